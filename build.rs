@@ -1,7 +1,12 @@
 use anyhow::*;
 use glob::glob;
 use std::fs::{read_to_string, write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+#[path = "build/layout.rs"]
+mod layout;
+
+const INCLUDE_DIR: &str = "./src/shaders/include";
 
 struct ShaderData {
     src: String,
@@ -24,9 +29,7 @@ impl ShaderData {
             _ => bail!("Unsupported shader: {}", src_path.display()),
         };
 
-        //let mut common = read_to_string("./src/shaders/common.glsl")?;
         let src = read_to_string(src_path.clone())?;
-        //common.push_str(&src);
         let spv_path = src_path.with_extension(format!("{}.spv", extension));
 
         Ok(Self {
@@ -38,6 +41,29 @@ impl ShaderData {
     }
 }
 
+/// Resolves `#include "file"` against the directory of the file doing the including, and
+/// `#include <file>` against the shared include directory.
+fn resolve_include(
+    requested: &str,
+    ty: shaderc::IncludeType,
+    requesting: &str,
+    _depth: usize,
+) -> shaderc::IncludeCallbackResult {
+    let path = match ty {
+        shaderc::IncludeType::Relative => Path::new(requesting)
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join(requested),
+        shaderc::IncludeType::Standard => Path::new(INCLUDE_DIR).join(requested),
+    };
+    read_to_string(&path)
+        .map(|content| shaderc::ResolvedInclude {
+            resolved_name: path.to_str().unwrap().to_owned(),
+            content,
+        })
+        .map_err(|e| format!("Unable to include {}: {}", path.display(), e))
+}
+
 fn main() -> Result<()> {
     // Collect all shaders recursively within /src/
     let mut shader_paths = [
@@ -55,7 +81,21 @@ fn main() -> Result<()> {
         .collect::<Result<Vec<_>>>()?;
 
     let mut compiler = shaderc::Compiler::new().context("Unable to create shader compiler")?;
+    let mut options =
+        shaderc::CompileOptions::new().context("Unable to create shader compile options")?;
+    options.set_include_callback(resolve_include);
 
+    // Headers aren't compiled on their own, but every shader may depend on them
+    for header in glob(&format!("{}/*.glsl", INCLUDE_DIR))? {
+        println!("cargo:rerun-if-changed={}", header?.display());
+    }
+
+    // The Rust side of the layout check
+    for (path, _) in layout::SHARED_STRUCTS {
+        println!("cargo:rerun-if-changed={}", path);
+    }
+
+    let mut compute_shaders = Vec::new();
     for shader in shaders {
         // This tells cargo to rerun this script if something in /src/ changes.
         println!(
@@ -68,10 +108,15 @@ fn main() -> Result<()> {
             shader.kind,
             &shader.src_path.to_str().unwrap(),
             "main",
-            None,
+            Some(&options),
         )?;
         write(shader.spv_path, compiled.as_binary_u8())?;
+
+        if shader.kind == shaderc::ShaderKind::Compute {
+            let name = shader.src_path.file_name().unwrap().to_string_lossy();
+            compute_shaders.push((name.into_owned(), compiled.as_binary().to_vec()));
+        }
     }
 
-    Ok(())
+    layout::check(&compute_shaders)
 }
@@ -0,0 +1,435 @@
+//! Checks that the structs uploaded to the GPU have the same layout on both sides. The Rust
+//! layout is worked out from the struct definitions, which are all `#[repr(C)]`, and the GLSL
+//! one is read back from the offsets and strides in the compiled SPIR-V. Any difference fails
+//! the build, since the shaders would otherwise just read garbage.
+
+use anyhow::*;
+use std::collections::HashMap;
+use std::fs::read_to_string;
+
+/// Structs shared with the compute shaders and the file that defines each of them. Every one
+/// needs a GLSL struct or block of the same name in at least one compute shader.
+pub const SHARED_STRUCTS: &[(&str, &str)] = &[
+    ("./src/data_types.rs", "Ray"),
+    ("./src/data_types.rs", "PathSegment"),
+    ("./src/data_types.rs", "Intersection"),
+    ("./src/data_types.rs", "Geometry"),
+    ("./src/data_types.rs", "MeshVertex"),
+    ("./src/data_types.rs", "MeshInfo"),
+    ("./src/data_types.rs", "BvhNode"),
+    ("./src/data_types.rs", "Material"),
+    ("./src/data_types.rs", "TextureInfo"),
+    ("./src/camera.rs", "Camera"),
+];
+
+/// A member as laid out in memory. Padding fields (named `_...`) aren't listed, so the
+/// members of both sides line up one to one.
+struct Member {
+    name: String,
+    offset: u32,
+    size: u32,
+}
+
+/// Compares every shared struct against its counterparts in the compiled shaders, given as
+/// their file names and SPIR-V.
+pub fn check(shaders: &[(String, Vec<u32>)]) -> Result<()> {
+    let rust = RustTypes::parse()?;
+    let modules = shaders
+        .iter()
+        .map(|(name, words)| Ok((name, Module::parse(words).context(name.clone())?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut errors = Vec::new();
+    for &(_, name) in SHARED_STRUCTS {
+        let (rust_members, rust_size) = rust.members(name)?;
+        let mut found = false;
+
+        for (shader, module) in &modules {
+            for id in module.structs_named(name) {
+                found = true;
+                let glsl_members = module.members(id);
+                if glsl_members.len() != rust_members.len() {
+                    errors.push(format!(
+                        "{} has {} members in Rust but {} in {}",
+                        name,
+                        rust_members.len(),
+                        glsl_members.len(),
+                        shader
+                    ));
+                    continue;
+                }
+                for (r, g) in rust_members.iter().zip(&glsl_members) {
+                    if r.offset != g.offset || r.size != g.size {
+                        errors.push(format!(
+                            "{}.{} is at offset {} ({} bytes) in Rust, but {} is at offset {} \
+                             ({} bytes) in {}",
+                            name, r.name, r.offset, r.size, g.name, g.offset, g.size, shader
+                        ));
+                    }
+                }
+
+                // Arrays have to step over whole Rust structs, and a lone block can't reach
+                // past the end of what gets uploaded for it
+                match module.array_stride(id) {
+                    Some(stride) if stride != rust_size => errors.push(format!(
+                        "{} is {} bytes in Rust, but arrays of it in {} have a stride of {}",
+                        name, rust_size, shader, stride
+                    )),
+                    None if module.unpadded_size(id) > rust_size => errors.push(format!(
+                        "{} is {} bytes in Rust, but {} bytes in {}",
+                        name,
+                        rust_size,
+                        module.unpadded_size(id),
+                        shader
+                    )),
+                    _ => {}
+                }
+            }
+        }
+
+        if !found {
+            errors.push(format!("{} isn't used by any compute shader", name));
+        }
+    }
+
+    if !errors.is_empty() {
+        bail!(
+            "Rust and GLSL struct layouts don't match:\n  {}",
+            errors.join("\n  ")
+        );
+    }
+    Ok(())
+}
+
+fn round_up(value: u32, align: u32) -> u32 {
+    // Alignments are always powers of two
+    (value + align - 1) & !(align - 1)
+}
+
+/// The `#[repr(C)]` structs and `bitflags!` types from the files in `SHARED_STRUCTS`.
+struct RustTypes {
+    structs: HashMap<String, syn::ItemStruct>,
+    // Bitflags type name to the integer type it wraps
+    flags: HashMap<String, String>,
+}
+
+impl RustTypes {
+    fn parse() -> Result<Self> {
+        let mut types = Self {
+            structs: HashMap::new(),
+            flags: HashMap::new(),
+        };
+        let mut paths = SHARED_STRUCTS
+            .iter()
+            .map(|(path, _)| *path)
+            .collect::<Vec<_>>();
+        paths.dedup();
+
+        for path in paths {
+            let file = syn::parse_file(&read_to_string(path)?)
+                .with_context(|| format!("Unable to parse {}", path))?;
+            for item in file.items {
+                match item {
+                    syn::Item::Struct(item) => {
+                        types.structs.insert(item.ident.to_string(), item);
+                    }
+                    syn::Item::Macro(item)
+                        if item.mac.path.segments.last().unwrap().ident == "bitflags" =>
+                    {
+                        types.add_flags(item.mac.tokens);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(types)
+    }
+
+    /// Picks `Name: Int` out of every `struct Name: Int { ... }` in a `bitflags!` invocation.
+    fn add_flags(&mut self, tokens: proc_macro2::TokenStream) {
+        use proc_macro2::TokenTree;
+        let tokens = tokens.into_iter().collect::<Vec<_>>();
+        for window in tokens.windows(4) {
+            if let [TokenTree::Ident(keyword), TokenTree::Ident(name), TokenTree::Punct(colon), TokenTree::Ident(int)] =
+                window
+            {
+                if keyword == "struct" && colon.as_char() == ':' {
+                    self.flags.insert(name.to_string(), int.to_string());
+                }
+            }
+        }
+    }
+
+    /// The members of a struct without its padding, and its size.
+    fn members(&self, name: &str) -> Result<(Vec<Member>, u32)> {
+        let (members, size, _) = self.struct_layout(name)?;
+        Ok((members, size))
+    }
+
+    /// Lays a struct out by the `repr(C)` rules, returning its members, size and alignment.
+    fn struct_layout(&self, name: &str) -> Result<(Vec<Member>, u32, u32)> {
+        let item = self
+            .structs
+            .get(name)
+            .with_context(|| format!("No struct named {}", name))?;
+        let repr_c = item
+            .attrs
+            .iter()
+            .any(|attr| attr.path.is_ident("repr") && attr.tokens.to_string().contains('C'));
+        ensure!(
+            repr_c,
+            "{} has to be #[repr(C)] to have a fixed layout",
+            name
+        );
+
+        let mut members = Vec::new();
+        let mut offset = 0;
+        let mut struct_align = 1;
+        for field in &item.fields {
+            let field_name = field.ident.as_ref().unwrap().to_string();
+            let (size, align) = self
+                .layout(&field.ty)
+                .with_context(|| format!("Unsupported type for {}.{}", name, field_name))?;
+            offset = round_up(offset, align);
+            if !field_name.starts_with('_') {
+                members.push(Member {
+                    name: field_name,
+                    offset,
+                    size,
+                });
+            }
+            offset += size;
+            struct_align = struct_align.max(align);
+        }
+        Ok((members, round_up(offset, struct_align), struct_align))
+    }
+
+    /// Size and alignment of a field type.
+    fn layout(&self, ty: &syn::Type) -> Result<(u32, u32)> {
+        match ty {
+            syn::Type::Path(path) => {
+                let name = path.path.segments.last().unwrap().ident.to_string();
+                match name.as_str() {
+                    "u32" | "i32" | "f32" => Ok((4, 4)),
+                    _ if self.flags.contains_key(&name) => {
+                        self.layout(&syn::parse_str(&self.flags[&name])?)
+                    }
+                    _ => {
+                        let (_, size, align) = self.struct_layout(&name)?;
+                        Ok((size, align))
+                    }
+                }
+            }
+            syn::Type::Array(array) => {
+                let (size, align) = self.layout(&array.elem)?;
+                match &array.len {
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Int(len),
+                        ..
+                    }) => Ok((size * len.base10_parse::<u32>()?, align)),
+                    _ => bail!("Array lengths have to be literals"),
+                }
+            }
+            _ => bail!("Only scalars, arrays and structs are supported"),
+        }
+    }
+}
+
+enum Type {
+    // Size in bytes
+    Scalar(u32),
+    // Component type and count
+    Vector(u32, u32),
+    // Column type and count
+    Matrix(u32, u32),
+    // Element type and the constant holding the length
+    Array(u32, u32),
+    RuntimeArray(u32),
+    Struct(Vec<u32>),
+}
+
+/// The parts of a SPIR-V module that describe how its types are laid out.
+#[derive(Default)]
+struct Module {
+    names: HashMap<u32, String>,
+    member_names: HashMap<(u32, u32), String>,
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, u32>,
+    offsets: HashMap<(u32, u32), u32>,
+    matrix_strides: HashMap<(u32, u32), u32>,
+    array_strides: HashMap<u32, u32>,
+}
+
+// Opcodes and decorations, from the SPIR-V specification
+const OP_NAME: u32 = 5;
+const OP_MEMBER_NAME: u32 = 6;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_CONSTANT: u32 = 43;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_OFFSET: u32 = 35;
+
+impl Module {
+    fn parse(words: &[u32]) -> Result<Self> {
+        ensure!(
+            words.len() > 5 && words[0] == 0x0723_0203,
+            "Not a SPIR-V module"
+        );
+        let mut module = Self::default();
+        let mut rest = &words[5..];
+
+        while !rest.is_empty() {
+            let count = (rest[0] >> 16) as usize;
+            ensure!(count > 0 && count <= rest.len(), "Truncated instruction");
+            let (instruction, next) = rest.split_at(count);
+            rest = next;
+
+            let op = &instruction[1..];
+            match instruction[0] & 0xffff {
+                OP_NAME => {
+                    module.names.insert(op[0], string(&op[1..]));
+                }
+                OP_MEMBER_NAME => {
+                    module.member_names.insert((op[0], op[1]), string(&op[2..]));
+                }
+                OP_TYPE_INT | OP_TYPE_FLOAT => {
+                    module.types.insert(op[0], Type::Scalar(op[1] / 8));
+                }
+                OP_TYPE_VECTOR => {
+                    module.types.insert(op[0], Type::Vector(op[1], op[2]));
+                }
+                OP_TYPE_MATRIX => {
+                    module.types.insert(op[0], Type::Matrix(op[1], op[2]));
+                }
+                OP_TYPE_ARRAY => {
+                    module.types.insert(op[0], Type::Array(op[1], op[2]));
+                }
+                OP_TYPE_RUNTIME_ARRAY => {
+                    module.types.insert(op[0], Type::RuntimeArray(op[1]));
+                }
+                OP_TYPE_STRUCT => {
+                    module.types.insert(op[0], Type::Struct(op[1..].to_vec()));
+                }
+                OP_CONSTANT => {
+                    module.constants.insert(op[1], op[2]);
+                }
+                OP_DECORATE if op[1] == DECORATION_ARRAY_STRIDE => {
+                    module.array_strides.insert(op[0], op[2]);
+                }
+                OP_MEMBER_DECORATE if op[2] == DECORATION_OFFSET => {
+                    module.offsets.insert((op[0], op[1]), op[3]);
+                }
+                OP_MEMBER_DECORATE if op[2] == DECORATION_MATRIX_STRIDE => {
+                    module.matrix_strides.insert((op[0], op[1]), op[3]);
+                }
+                _ => {}
+            }
+        }
+        Ok(module)
+    }
+
+    /// Struct types called `name` that have an explicit layout. glslang also declares
+    /// undecorated copies for function local variables, which never touch memory.
+    fn structs_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = u32> + 'a {
+        self.types.iter().filter_map(move |(&id, ty)| match ty {
+            Type::Struct(_)
+                if self.names.get(&id).map(String::as_str) == Some(name)
+                    && self.offsets.contains_key(&(id, 0)) =>
+            {
+                Some(id)
+            }
+            _ => None,
+        })
+    }
+
+    fn members(&self, id: u32) -> Vec<Member> {
+        let count = match &self.types[&id] {
+            Type::Struct(members) => members.len() as u32,
+            _ => 0,
+        };
+        (0..count)
+            .map(|index| Member {
+                name: self
+                    .member_names
+                    .get(&(id, index))
+                    .cloned()
+                    .unwrap_or_else(|| index.to_string()),
+                offset: self.offsets[&(id, index)],
+                size: self.member_size(id, index),
+            })
+            .collect()
+    }
+
+    fn member_size(&self, id: u32, index: u32) -> u32 {
+        let ty = match &self.types[&id] {
+            Type::Struct(members) => members[index as usize],
+            _ => unreachable!(),
+        };
+        match (&self.types[&ty], self.matrix_strides.get(&(id, index))) {
+            (Type::Matrix(_, columns), Some(stride)) => columns * stride,
+            _ => self.size(ty),
+        }
+    }
+
+    /// Stride of the arrays of a struct, if it's used in any.
+    fn array_stride(&self, id: u32) -> Option<u32> {
+        self.types.iter().find_map(|(array, ty)| match ty {
+            Type::Array(element, _) | Type::RuntimeArray(element) if *element == id => {
+                self.array_strides.get(array).copied()
+            }
+            _ => None,
+        })
+    }
+
+    /// Where the last member of a struct ends.
+    fn unpadded_size(&self, id: u32) -> u32 {
+        self.members(id)
+            .iter()
+            .map(|member| member.offset + member.size)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn size(&self, id: u32) -> u32 {
+        match &self.types[&id] {
+            Type::Scalar(size) => *size,
+            Type::Vector(component, count) => self.size(*component) * count,
+            Type::Matrix(column, count) => round_up(self.size(*column), 16) * count,
+            Type::Array(_, length) => {
+                self.array_strides.get(&id).copied().unwrap_or(0) * self.constants[length]
+            }
+            Type::RuntimeArray(_) => 0,
+            Type::Struct(_) => round_up(self.unpadded_size(id), self.align(id)),
+        }
+    }
+
+    /// Base alignment under std430, which is what every shared struct uses.
+    fn align(&self, id: u32) -> u32 {
+        match &self.types[&id] {
+            Type::Scalar(size) => *size,
+            Type::Vector(component, 2) => self.size(*component) * 2,
+            Type::Vector(component, _) => self.size(*component) * 4,
+            Type::Matrix(column, _) => self.align(*column),
+            Type::Array(element, _) | Type::RuntimeArray(element) => self.align(*element),
+            Type::Struct(members) => members.iter().map(|&m| self.align(m)).max().unwrap_or(1),
+        }
+    }
+}
+
+/// Decodes a nul terminated literal string.
+fn string(words: &[u32]) -> String {
+    let bytes = words
+        .iter()
+        .flat_map(|word| word.to_le_bytes().to_vec())
+        .take_while(|&byte| byte != 0)
+        .collect::<Vec<_>>();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
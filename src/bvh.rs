@@ -0,0 +1,1104 @@
+//! Bounding volume hierarchies, built on the CPU and flattened into the node and primitive
+//! buffers the hit shader walks.
+//!
+//! Scenes use two levels: every mesh gets its own bottom level BVH over its triangles in
+//! object space, and one top level BVH over the geometry places them in the world. A mesh
+//! placed a thousand times still only has its triangles and tree uploaded once.
+
+use std::fmt;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use cgmath::{Matrix4, Point3, Transform};
+
+use crate::data_types::{BvhNode, GeomType, Geometry};
+use crate::mesh::Mesh;
+use crate::scene::Scene;
+
+/// Axis aligned bounding box in world space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    /// A box containing nothing, growing it by anything gives that thing's bounds.
+    pub fn empty() -> Self {
+        Self {
+            min: Point3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: Point3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn grow(&mut self, point: Point3<f32>) {
+        for axis in 0..3 {
+            self.min[axis] = self.min[axis].min(point[axis]);
+            self.max[axis] = self.max[axis].max(point[axis]);
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        let mut result = *self;
+        result.grow(other.min);
+        result.grow(other.max);
+        result
+    }
+
+    pub fn centroid(&self) -> Point3<f32> {
+        Point3::new(
+            0.5 * (self.min.x + self.max.x),
+            0.5 * (self.min.y + self.max.y),
+            0.5 * (self.min.z + self.max.z),
+        )
+    }
+
+    /// Axis the box is longest along.
+    pub fn largest_axis(&self) -> usize {
+        let extent = self.max - self.min;
+        if extent.x > extent.y && extent.x > extent.z {
+            0
+        } else if extent.y > extent.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let extent = self.max - self.min;
+        if extent.x < 0.0 || extent.y < 0.0 || extent.z < 0.0 {
+            return 0.0;
+        }
+        2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
+
+    /// Smallest box around this one after `transform`.
+    pub fn transformed(&self, transform: &Matrix4<f32>) -> Aabb {
+        let mut bounds = Aabb::empty();
+        for corner in 0..8 {
+            let x = if corner & 1 == 0 {
+                self.min.x
+            } else {
+                self.max.x
+            };
+            let y = if corner & 2 == 0 {
+                self.min.y
+            } else {
+                self.max.y
+            };
+            let z = if corner & 4 == 0 {
+                self.min.z
+            } else {
+                self.max.z
+            };
+            bounds.grow(transform.transform_point(Point3::new(x, y, z)));
+        }
+        bounds
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+}
+
+/// How the tree gets split.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitMethod {
+    /// Halves every node at the median centroid along its longest axis. Quick to build, but
+    /// ignores how primitives are spread out.
+    Median,
+    /// Buckets centroids into bins along each axis and takes the split with the lowest
+    /// surface area heuristic cost. Nodes only split when that beats making a leaf.
+    BinnedSah,
+}
+
+impl std::str::FromStr for SplitMethod {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "median" => Ok(SplitMethod::Median),
+            "sah" => Ok(SplitMethod::BinnedSah),
+            _ => Err(format!("Unknown BVH split method: {}", name)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BvhBuildOptions {
+    pub split: SplitMethod,
+    /// Nodes with more primitives than this are always split
+    pub max_leaf_size: usize,
+    /// Buckets per axis for `SplitMethod::BinnedSah`
+    pub bin_count: usize,
+    /// Refitting after edits is rebuilt from scratch once it makes the top level SAH cost
+    /// this many times worse than right after the last build
+    pub rebuild_threshold: f32,
+}
+
+impl Default for BvhBuildOptions {
+    fn default() -> Self {
+        Self {
+            split: SplitMethod::BinnedSah,
+            max_leaf_size: 4,
+            bin_count: 16,
+            rebuild_threshold: 1.5,
+        }
+    }
+}
+
+/// What a build produced, for comparing builders against each other.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BvhStats {
+    pub node_count: usize,
+    pub leaf_count: usize,
+    pub primitive_count: usize,
+    pub max_depth: usize,
+    /// Expected cost of a random ray through the tree, relative to one traversal step
+    pub sah_cost: f32,
+    pub build_time: Duration,
+}
+
+impl fmt::Display for BvhStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} nodes ({} leaves) over {} primitives, depth {}, SAH cost {:.2}, built in {:.1?}",
+            self.node_count,
+            self.leaf_count,
+            self.primitive_count,
+            self.max_depth,
+            self.sah_cost,
+            self.build_time
+        )
+    }
+}
+
+impl BvhStats {
+    /// Folds another tree into these totals, keeping the deepest depth.
+    pub fn accumulate(&mut self, other: &BvhStats) {
+        self.node_count += other.node_count;
+        self.leaf_count += other.leaf_count;
+        self.primitive_count += other.primitive_count;
+        self.max_depth = self.max_depth.max(other.max_depth);
+        self.sah_cost += other.sah_cost;
+        self.build_time += other.build_time;
+    }
+}
+
+/// A single BVH, see [`BvhNode`] for the layout. Leaves point into `primitives`, which holds
+/// whatever index the tree was built over.
+#[derive(Debug, Clone)]
+pub struct Bvh {
+    pub nodes: Vec<BvhNode>,
+    pub primitives: Vec<u32>,
+    pub stats: BvhStats,
+}
+
+impl Bvh {
+    /// Builds a bottom level BVH over the triangles of a mesh in object space. Primitives are
+    /// the first index of each triangle.
+    pub fn over_mesh(mesh: &Mesh, options: &BvhBuildOptions) -> Self {
+        let items = (0..mesh.indices.len()).step_by(3).map(|first| {
+            let mut bounds = Aabb::empty();
+            for &index in &mesh.indices[first..first + 3] {
+                bounds.grow(Point3::from(mesh.vertices[index as usize].position));
+            }
+            (first as u32, bounds)
+        });
+        Self::build(items, options)
+    }
+
+    /// Builds a BVH over anything with bounds. Empty bounds are left out of the tree.
+    pub fn build(primitives: impl Iterator<Item = (u32, Aabb)>, options: &BvhBuildOptions) -> Self {
+        let start = Instant::now();
+
+        let mut items: Vec<BuildItem> = primitives
+            .filter(|(_, bounds)| !bounds.is_empty())
+            .map(|(primitive, bounds)| BuildItem {
+                primitive,
+                bounds,
+                centroid: bounds.centroid(),
+            })
+            .collect();
+
+        let mut builder = Builder {
+            options,
+            nodes: Vec::with_capacity(2 * items.len() / options.max_leaf_size.max(1) + 1),
+            max_depth: 0,
+        };
+        if items.is_empty() {
+            // Nothing to hit, a zeroed root tells the shader the tree is empty
+            builder.nodes.push(BvhNode::default());
+        } else {
+            builder.build_recursive(&mut items, 0, 0);
+        }
+
+        let nodes = builder.nodes;
+        let stats = BvhStats {
+            node_count: nodes.len(),
+            leaf_count: nodes.iter().filter(|node| node.count > 0).count(),
+            primitive_count: items.len(),
+            max_depth: builder.max_depth,
+            sah_cost: sah_cost(&nodes),
+            build_time: start.elapsed(),
+        };
+
+        Self {
+            nodes,
+            primitives: items.iter().map(|item| item.primitive).collect(),
+            stats,
+        }
+    }
+
+    /// Builds a top level BVH over a scene's geometry, see [`geometry_bounds`].
+    pub fn over_geometry(
+        geometry: &[Geometry],
+        mesh_bounds: &[Aabb],
+        options: &BvhBuildOptions,
+    ) -> Self {
+        let items = geometry
+            .iter()
+            .enumerate()
+            .map(|(index, geometry)| (index as u32, geometry_bounds(geometry, mesh_bounds)));
+        Self::build(items, options)
+    }
+
+    /// Recomputes every node's bounds from `bounds_of` each primitive, keeping the shape of
+    /// the tree. Much cheaper than a rebuild, but the tree gets worse the further things move
+    /// from where they were built. Returns the range of nodes whose bounds changed.
+    pub fn refit(&mut self, bounds_of: impl Fn(u32) -> Aabb) -> Option<Range<usize>> {
+        if is_empty_tree(&self.nodes[0]) {
+            return None;
+        }
+
+        let mut changed: Option<Range<usize>> = None;
+        // Children always come after their parent, so walking backwards sees them first
+        for index in (0..self.nodes.len()).rev() {
+            let node = self.nodes[index];
+            let bounds = if node.count > 0 {
+                let first = node.offset as usize;
+                self.primitives[first..first + node.count as usize]
+                    .iter()
+                    .fold(Aabb::empty(), |bounds, &primitive| {
+                        bounds.union(&bounds_of(primitive))
+                    })
+            } else {
+                node_bounds(&self.nodes[index + 1])
+                    .union(&node_bounds(&self.nodes[node.offset as usize]))
+            };
+
+            let min: [f32; 3] = bounds.min.into();
+            let max: [f32; 3] = bounds.max.into();
+            if min != node.min || max != node.max {
+                self.nodes[index].min = min;
+                self.nodes[index].max = max;
+                changed = Some(match changed {
+                    Some(range) => index..range.end,
+                    None => index..index + 1,
+                });
+            }
+        }
+
+        self.stats.sah_cost = sah_cost(&self.nodes);
+        changed
+    }
+
+    /// Refits a top level tree around where `geometry` is now, or rebuilds it once refitting
+    /// leaves it more than `options.rebuild_threshold` times as costly as `built_cost`.
+    pub fn update_top_level(
+        &mut self,
+        geometry: &[Geometry],
+        mesh_bounds: &[Aabb],
+        built_cost: f32,
+        options: &BvhBuildOptions,
+    ) -> TopLevelUpdate {
+        let changed = self.refit(|index| geometry_bounds(&geometry[index as usize], mesh_bounds));
+        if self.stats.sah_cost > built_cost * options.rebuild_threshold {
+            *self = Bvh::over_geometry(geometry, mesh_bounds, options);
+            TopLevelUpdate::Rebuilt
+        } else {
+            TopLevelUpdate::Refit(changed)
+        }
+    }
+
+    /// Root bounds, empty when the tree is.
+    pub fn bounds(&self) -> Aabb {
+        let root = &self.nodes[0];
+        if is_empty_tree(root) {
+            Aabb::empty()
+        } else {
+            node_bounds(root)
+        }
+    }
+}
+
+/// What [`Bvh::update_top_level`] did to the tree, to know which parts to upload again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TopLevelUpdate {
+    /// Refit in place, only the nodes in the range changed
+    Refit(Option<Range<usize>>),
+    /// Built again from scratch, every node and primitive may have changed
+    Rebuilt,
+}
+
+/// Most nodes and primitives a top level tree over `geometry_count` pieces of geometry can
+/// need. A binary tree over n leaves has 2n - 1 nodes, and there are never more leaves than
+/// primitives.
+pub fn top_level_capacity(geometry_count: usize) -> (usize, usize) {
+    (geometry_count.max(1) * 2 - 1, geometry_count.max(1))
+}
+
+fn is_empty_tree(root: &BvhNode) -> bool {
+    root.count == 0 && root.offset == 0
+}
+
+fn node_bounds(node: &BvhNode) -> Aabb {
+    Aabb {
+        min: Point3::from(node.min),
+        max: Point3::from(node.max),
+    }
+}
+
+/// Both levels of a scene's acceleration structure.
+#[derive(Debug, Clone)]
+pub struct SceneBvh {
+    /// Over the scene's geometry, primitives are indices into `Scene::geometry`
+    pub top_level: Bvh,
+    /// One per mesh, in the order of `Scene::meshes`
+    pub bottom_level: Vec<Bvh>,
+    // Room is kept for the top level to be rebuilt over all of it
+    geometry_count: usize,
+}
+
+/// Every tree of a [`SceneBvh`] packed into one node and one primitive list. The top level
+/// starts at node 0 and is padded out to [`top_level_capacity`], so it can be rebuilt in
+/// place. `mesh_roots` says where each mesh's tree starts.
+#[derive(Debug, Clone)]
+pub struct FlatBvh {
+    pub nodes: Vec<BvhNode>,
+    pub primitives: Vec<u32>,
+    pub mesh_roots: Vec<u32>,
+}
+
+impl SceneBvh {
+    pub fn build(scene: &Scene, options: &BvhBuildOptions) -> Self {
+        let bottom_level: Vec<Bvh> = scene
+            .meshes
+            .iter()
+            .map(|mesh| Bvh::over_mesh(mesh, options))
+            .collect();
+
+        let mesh_bounds: Vec<Aabb> = bottom_level.iter().map(Bvh::bounds).collect();
+        let top_level = Bvh::over_geometry(&scene.geometry, &mesh_bounds, options);
+
+        Self {
+            top_level,
+            bottom_level,
+            geometry_count: scene.geometry.len(),
+        }
+    }
+
+    /// Object space bounds of every mesh.
+    pub fn mesh_bounds(&self) -> Vec<Aabb> {
+        self.bottom_level.iter().map(Bvh::bounds).collect()
+    }
+
+    /// Top level stats, and the bottom level ones summed over every mesh.
+    pub fn stats(&self) -> SceneBvhStats {
+        let mut bottom_level = BvhStats::default();
+        for bvh in &self.bottom_level {
+            bottom_level.accumulate(&bvh.stats);
+        }
+        SceneBvhStats {
+            top_level: self.top_level.stats,
+            bottom_level,
+            mesh_count: self.bottom_level.len(),
+        }
+    }
+
+    pub fn flatten(&self) -> FlatBvh {
+        let mut flat = FlatBvh {
+            nodes: Vec::new(),
+            primitives: Vec::new(),
+            mesh_roots: Vec::with_capacity(self.bottom_level.len()),
+        };
+        append_bvh(&mut flat, &self.top_level);
+        let (node_capacity, primitive_capacity) = top_level_capacity(self.geometry_count);
+        flat.nodes.resize(node_capacity, BvhNode::default());
+        flat.primitives.resize(primitive_capacity, 0);
+        for bvh in &self.bottom_level {
+            let root = append_bvh(&mut flat, bvh);
+            flat.mesh_roots.push(root);
+        }
+        flat
+    }
+}
+
+/// How both levels turned out. The bottom level is summed over every mesh, with the deepest
+/// of their depths.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SceneBvhStats {
+    pub top_level: BvhStats,
+    pub bottom_level: BvhStats,
+    pub mesh_count: usize,
+}
+
+impl fmt::Display for SceneBvhStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "top level {}; bottom level over {} meshes {}",
+            self.top_level, self.mesh_count, self.bottom_level
+        )
+    }
+}
+
+/// World space bounds of a piece of geometry, meshes are looked up in `mesh_bounds`.
+pub fn geometry_bounds(geometry: &Geometry, mesh_bounds: &[Aabb]) -> Aabb {
+    let object_bounds = if geometry.ty == GeomType::TRIANGLE {
+        mesh_bounds[geometry.mesh_id as usize]
+    } else {
+        // Unit sphere and unit cube
+        let half = if geometry.ty == GeomType::SPHERE {
+            1.0
+        } else {
+            0.5
+        };
+        Aabb {
+            min: Point3::new(-half, -half, -half),
+            max: Point3::new(half, half, half),
+        }
+    };
+    if object_bounds.is_empty() {
+        return object_bounds;
+    }
+    object_bounds.transformed(&Matrix4::from(geometry.transf))
+}
+
+// Appends a tree with its offsets moved past what is already there, returns its root
+fn append_bvh(flat: &mut FlatBvh, bvh: &Bvh) -> u32 {
+    let node_base = flat.nodes.len() as u32;
+    let primitive_base = flat.primitives.len() as u32;
+    flat.nodes.extend(bvh.nodes.iter().map(|node| {
+        let mut node = *node;
+        if node.count > 0 {
+            node.offset += primitive_base;
+        } else if node.offset != 0 {
+            node.offset += node_base;
+        }
+        node
+    }));
+    flat.primitives.extend_from_slice(&bvh.primitives);
+    node_base
+}
+
+/// Relative cost of stepping through a node and of testing one primitive.
+const TRAVERSAL_COST: f32 = 1.0;
+const INTERSECTION_COST: f32 = 1.0;
+
+/// Deeper trees would overflow the traversal stack in `calculate_intersections.comp`, nodes
+/// this deep become leaves no matter how big they are.
+const MAX_DEPTH: usize = 60;
+
+struct BuildItem {
+    primitive: u32,
+    bounds: Aabb,
+    centroid: Point3<f32>,
+}
+
+struct Builder<'a> {
+    options: &'a BvhBuildOptions,
+    nodes: Vec<BvhNode>,
+    max_depth: usize,
+}
+
+#[derive(Clone, Copy)]
+struct Bin {
+    bounds: Aabb,
+    count: usize,
+}
+
+impl Builder<'_> {
+    // `first` is where `items` starts in the final primitive list
+    fn build_recursive(&mut self, items: &mut [BuildItem], first: usize, depth: usize) -> usize {
+        self.max_depth = self.max_depth.max(depth);
+
+        let bounds = items
+            .iter()
+            .fold(Aabb::empty(), |bounds, item| bounds.union(&item.bounds));
+        let node_index = self.nodes.len();
+        self.nodes.push(BvhNode {
+            min: bounds.min.into(),
+            max: bounds.max.into(),
+            ..BvhNode::default()
+        });
+
+        let mid = if items.len() <= 1 || depth >= MAX_DEPTH {
+            None
+        } else {
+            match self.options.split {
+                SplitMethod::Median => {
+                    if items.len() <= self.options.max_leaf_size {
+                        None
+                    } else {
+                        Some(median_split(items))
+                    }
+                }
+                SplitMethod::BinnedSah => self.sah_split(items, &bounds),
+            }
+        };
+
+        let mid = match mid {
+            Some(mid) => mid,
+            None => {
+                self.nodes[node_index].offset = first as u32;
+                self.nodes[node_index].count = items.len() as u32;
+                return node_index;
+            }
+        };
+
+        let (left, right) = items.split_at_mut(mid);
+        self.build_recursive(left, first, depth + 1);
+        let right_index = self.build_recursive(right, first + mid, depth + 1);
+        self.nodes[node_index].offset = right_index as u32;
+        node_index
+    }
+
+    // Partitions `items` at the cheapest bin boundary and returns where the right half
+    // starts, or None when a leaf is cheaper
+    fn sah_split(&self, items: &mut [BuildItem], bounds: &Aabb) -> Option<usize> {
+        let mut centroid_bounds = Aabb::empty();
+        for item in items.iter() {
+            centroid_bounds.grow(item.centroid);
+        }
+
+        let bin_count = self.options.bin_count.max(2);
+        let mut best = None;
+        let mut best_cost = f32::INFINITY;
+        for axis in 0..3 {
+            let low = centroid_bounds.min[axis];
+            let extent = centroid_bounds.max[axis] - low;
+            if extent <= 0.0 {
+                continue;
+            }
+
+            let mut bins = vec![
+                Bin {
+                    bounds: Aabb::empty(),
+                    count: 0,
+                };
+                bin_count
+            ];
+            for item in items.iter() {
+                let bin = &mut bins[bin_index(item.centroid[axis], low, extent, bin_count)];
+                bin.bounds = bin.bounds.union(&item.bounds);
+                bin.count += 1;
+            }
+
+            // Sweep from the right first so every boundary knows what lies past it
+            let mut right_area = vec![0.0; bin_count];
+            let mut right_count = vec![0; bin_count];
+            let mut accumulated = Bin {
+                bounds: Aabb::empty(),
+                count: 0,
+            };
+            for split in (1..bin_count).rev() {
+                accumulated.bounds = accumulated.bounds.union(&bins[split].bounds);
+                accumulated.count += bins[split].count;
+                right_area[split] = accumulated.bounds.surface_area();
+                right_count[split] = accumulated.count;
+            }
+
+            let mut left = Bin {
+                bounds: Aabb::empty(),
+                count: 0,
+            };
+            for split in 1..bin_count {
+                left.bounds = left.bounds.union(&bins[split - 1].bounds);
+                left.count += bins[split - 1].count;
+                if left.count == 0 || right_count[split] == 0 {
+                    continue;
+                }
+                let cost = left.bounds.surface_area() * left.count as f32
+                    + right_area[split] * right_count[split] as f32;
+                if cost < best_cost {
+                    best_cost = cost;
+                    best = Some((axis, split));
+                }
+            }
+        }
+
+        let area = bounds.surface_area();
+        let leaf_cost = INTERSECTION_COST * items.len() as f32;
+        let (axis, split) = match best {
+            Some(best) => best,
+            // Every centroid is in the same spot, so no plane separates them
+            None if items.len() > self.options.max_leaf_size => {
+                return Some(median_split(items));
+            }
+            None => return None,
+        };
+        let split_cost = if area > 0.0 {
+            TRAVERSAL_COST + INTERSECTION_COST * best_cost / area
+        } else {
+            TRAVERSAL_COST + leaf_cost
+        };
+        if split_cost >= leaf_cost && items.len() <= self.options.max_leaf_size {
+            return None;
+        }
+
+        let low = centroid_bounds.min[axis];
+        let extent = centroid_bounds.max[axis] - low;
+        let mid = partition(items, |item| {
+            bin_index(item.centroid[axis], low, extent, bin_count) < split
+        });
+        Some(mid)
+    }
+}
+
+fn bin_index(value: f32, low: f32, extent: f32, bin_count: usize) -> usize {
+    let bin = ((value - low) / extent * bin_count as f32) as usize;
+    bin.min(bin_count - 1)
+}
+
+fn median_split(items: &mut [BuildItem]) -> usize {
+    let mut centroid_bounds = Aabb::empty();
+    for item in items.iter() {
+        centroid_bounds.grow(item.centroid);
+    }
+    let axis = centroid_bounds.largest_axis();
+
+    let mid = items.len() / 2;
+    items.select_nth_unstable_by(mid, |a, b| {
+        a.centroid[axis]
+            .partial_cmp(&b.centroid[axis])
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    mid
+}
+
+// Moves every item matching `predicate` to the front and returns how many there were
+fn partition(items: &mut [BuildItem], predicate: impl Fn(&BuildItem) -> bool) -> usize {
+    let mut mid = 0;
+    for i in 0..items.len() {
+        if predicate(&items[i]) {
+            items.swap(i, mid);
+            mid += 1;
+        }
+    }
+    mid
+}
+
+// Sums every node's cost weighted by the chance a ray through the root also hits it
+fn sah_cost(nodes: &[BvhNode]) -> f32 {
+    let area = |node: &BvhNode| {
+        Aabb {
+            min: Point3::from(node.min),
+            max: Point3::from(node.max),
+        }
+        .surface_area()
+    };
+
+    let root_area = area(&nodes[0]);
+    if root_area <= 0.0 {
+        return 0.0;
+    }
+    nodes
+        .iter()
+        .map(|node| {
+            let cost = if node.count > 0 {
+                INTERSECTION_COST * node.count as f32
+            } else {
+                TRAVERSAL_COST
+            };
+            cost * area(node) / root_area
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::{Geometry, MeshVertex};
+    use crate::mesh::Mesh;
+    use cgmath::Vector3;
+
+    // A grid of spheres and boxes, and a strip of triangles placed twice
+    fn test_scene() -> Scene {
+        let mut scene = Scene::empty();
+        for i in 0..40 {
+            let ty = if i % 3 == 0 {
+                GeomType::BOX
+            } else {
+                GeomType::SPHERE
+            };
+            let position = Vector3::new((i % 5) as f32 * 3.0, (i / 5 % 4) as f32, (i / 20) as f32);
+            scene.add_geometry(Geometry::new(ty, Matrix4::from_translation(position), 0));
+        }
+
+        let vertices = (0..8)
+            .map(|i| MeshVertex::new([i as f32 / 2.0, (i % 2) as f32, 0.0], [0.0; 3], [0.0; 2]))
+            .collect();
+        let indices = (0..6).flat_map(|i| vec![i, i + 1, i + 2]).collect();
+        let mesh_id = scene.add_mesh(Mesh::new(vertices, indices));
+        for z in [-5.0, 5.0].iter() {
+            let transform = Matrix4::from_translation(Vector3::new(0.0, 0.0, *z));
+            scene.add_geometry(Geometry::mesh(mesh_id, transform, 0));
+        }
+        scene
+    }
+
+    fn bounds(node: &BvhNode) -> Aabb {
+        Aabb {
+            min: Point3::from(node.min),
+            max: Point3::from(node.max),
+        }
+    }
+
+    fn contains(outer: &Aabb, inner: &Aabb) -> bool {
+        (0..3).all(|axis| outer.min[axis] <= inner.min[axis] && inner.max[axis] <= outer.max[axis])
+    }
+
+    // Every leaf reachable from the root, as node indices
+    fn leaves(nodes: &[BvhNode]) -> Vec<usize> {
+        let mut leaves = Vec::new();
+        if is_empty_tree(&nodes[0]) {
+            return leaves;
+        }
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &nodes[index];
+            if node.count > 0 {
+                leaves.push(index);
+            } else {
+                stack.push(index + 1);
+                stack.push(node.offset as usize);
+            }
+        }
+        leaves
+    }
+
+    // Every primitive in those leaves, sorted
+    fn reached(bvh: &Bvh) -> Vec<u32> {
+        let mut reached: Vec<u32> = leaves(&bvh.nodes)
+            .into_iter()
+            .flat_map(|leaf| {
+                let node = bvh.nodes[leaf];
+                let first = node.offset as usize;
+                bvh.primitives[first..first + node.count as usize].to_vec()
+            })
+            .collect();
+        reached.sort_unstable();
+        reached
+    }
+
+    // Walks the tree checking every node holds its children, and every leaf its primitives
+    fn check_bounds(bvh: &Bvh, bounds_of: impl Fn(u32) -> Aabb, max_leaf_size: usize) {
+        for leaf in leaves(&bvh.nodes) {
+            let node = &bvh.nodes[leaf];
+            assert!(node.count as usize <= max_leaf_size);
+            let first = node.offset as usize;
+            for &primitive in &bvh.primitives[first..first + node.count as usize] {
+                assert!(contains(&bounds(node), &bounds_of(primitive)));
+            }
+        }
+        for (index, node) in bvh.nodes.iter().enumerate() {
+            if node.count == 0 && node.offset != 0 {
+                for &child in &[index + 1, node.offset as usize] {
+                    assert!(contains(&bounds(node), &bounds(&bvh.nodes[child])));
+                }
+            }
+        }
+    }
+
+    fn triangle_bounds(mesh: &Mesh, first: u32) -> Aabb {
+        let mut bounds = Aabb::empty();
+        for &index in &mesh.indices[first as usize..first as usize + 3] {
+            bounds.grow(Point3::from(mesh.vertices[index as usize].position));
+        }
+        bounds
+    }
+
+    #[test]
+    fn every_primitive_reachable_once() {
+        let scene = test_scene();
+        let bvh = SceneBvh::build(&scene, &BvhBuildOptions::default());
+
+        let geometry: Vec<u32> = (0..scene.geometry.len() as u32).collect();
+        assert_eq!(reached(&bvh.top_level), geometry);
+        assert_eq!(bvh.bottom_level.len(), 1);
+        let triangles: Vec<u32> = (0..18).step_by(3).collect();
+        assert_eq!(reached(&bvh.bottom_level[0]), triangles);
+    }
+
+    #[test]
+    fn nodes_contain_their_children() {
+        let scene = test_scene();
+        let options = BvhBuildOptions::default();
+        let bvh = SceneBvh::build(&scene, &options);
+
+        let mesh_bounds = bvh.mesh_bounds();
+        check_bounds(
+            &bvh.top_level,
+            |index| geometry_bounds(&scene.geometry[index as usize], &mesh_bounds),
+            options.max_leaf_size,
+        );
+        check_bounds(
+            &bvh.bottom_level[0],
+            |first| triangle_bounds(&scene.meshes[0], first),
+            options.max_leaf_size,
+        );
+    }
+
+    #[test]
+    fn empty_scene() {
+        let bvh = SceneBvh::build(&Scene::empty(), &BvhBuildOptions::default());
+        let top_level = &bvh.top_level;
+        assert_eq!(top_level.nodes.len(), 1);
+        assert_eq!(
+            (top_level.nodes[0].offset, top_level.nodes[0].count),
+            (0, 0)
+        );
+        assert!(top_level.primitives.is_empty());
+        assert!(top_level.bounds().is_empty());
+        assert!(bvh.bottom_level.is_empty());
+    }
+
+    #[test]
+    fn one_primitive() {
+        let mut scene = Scene::empty();
+        scene.add_geometry(Geometry::new(GeomType::SPHERE, Matrix4::from_scale(2.0), 0));
+        let bvh = SceneBvh::build(&scene, &BvhBuildOptions::default());
+
+        let top_level = &bvh.top_level;
+        assert_eq!(top_level.nodes.len(), 1);
+        assert_eq!(
+            (top_level.nodes[0].offset, top_level.nodes[0].count),
+            (0, 1)
+        );
+        assert_eq!(top_level.nodes[0].min, [-2.0; 3]);
+        assert_eq!(top_level.nodes[0].max, [2.0; 3]);
+    }
+
+    #[test]
+    fn leaves_respect_max_leaf_size() {
+        let scene = test_scene();
+        for &split in &[SplitMethod::Median, SplitMethod::BinnedSah] {
+            for &max_leaf_size in &[1, 2, 4, 8] {
+                let options = BvhBuildOptions {
+                    split,
+                    max_leaf_size,
+                    ..BvhBuildOptions::default()
+                };
+                let bvh = SceneBvh::build(&scene, &options);
+                for tree in std::iter::once(&bvh.top_level).chain(&bvh.bottom_level) {
+                    for leaf in leaves(&tree.nodes) {
+                        assert!(
+                            tree.nodes[leaf].count as usize <= max_leaf_size,
+                            "{:?} split made a leaf bigger than {}",
+                            split,
+                            max_leaf_size
+                        );
+                    }
+                    assert_eq!(reached(tree).len(), tree.stats.primitive_count);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn depth_limited_on_degenerate_input() {
+        // Unit boxes around each position
+        let place = |positions: &mut dyn Iterator<Item = f32>| -> Vec<(u32, Aabb)> {
+            positions
+                .enumerate()
+                .map(|(i, x)| {
+                    let bounds = Aabb {
+                        min: Point3::new(x - 0.5, -0.5, -0.5),
+                        max: Point3::new(x + 0.5, 0.5, 0.5),
+                    };
+                    (i as u32, bounds)
+                })
+                .collect()
+        };
+        let options = BvhBuildOptions {
+            max_leaf_size: 1,
+            ..BvhBuildOptions::default()
+        };
+
+        // Every centroid in the same spot, so no plane separates any of them
+        let stacked = place(&mut (0..100).map(|_| 0.0));
+        let bvh = Bvh::build(stacked.into_iter(), &options);
+        assert!(bvh.stats.max_depth <= MAX_DEPTH);
+        assert_eq!(reached(&bvh).len(), 100);
+
+        // Each centroid three times further out than the last, so halving the range only ever
+        // splits off the outermost one and the tree would be 69 levels deep
+        let spread = place(&mut (0..70).map(|i| 3f32.powi(i)));
+        let options = BvhBuildOptions {
+            bin_count: 2,
+            ..options
+        };
+        let bvh = Bvh::build(spread.into_iter(), &options);
+        assert_eq!(bvh.stats.max_depth, MAX_DEPTH);
+        assert_eq!(reached(&bvh).len(), 70);
+    }
+
+    // BvhNode is plain old data, comparing its bytes is comparing every field
+    fn same_node(a: &BvhNode, b: &BvhNode) -> bool {
+        bytemuck::bytes_of(a) == bytemuck::bytes_of(b)
+    }
+
+    fn moved(scene: &mut Scene, index: usize, offset: Vector3<f32>) {
+        let transform = Matrix4::from(scene.geometry[index].transf);
+        scene.set_transform(index, Matrix4::from_translation(offset) * transform);
+    }
+
+    #[test]
+    fn refit_contains_moved_geometry() {
+        let mut scene = test_scene();
+        let options = BvhBuildOptions::default();
+        let mut bvh = SceneBvh::build(&scene, &options);
+        let mesh_bounds = bvh.mesh_bounds();
+
+        moved(&mut scene, 3, Vector3::new(0.0, 20.0, 0.0));
+        moved(&mut scene, 40, Vector3::new(-4.0, 1.0, 0.0));
+        bvh.top_level
+            .refit(|index| geometry_bounds(&scene.geometry[index as usize], &mesh_bounds));
+
+        let bounds_of = |index| geometry_bounds(&scene.geometry[index as usize], &mesh_bounds);
+        check_bounds(&bvh.top_level, bounds_of, options.max_leaf_size);
+        let all = (0..scene.geometry.len() as u32)
+            .fold(Aabb::empty(), |all, index| all.union(&bounds_of(index)));
+        assert_eq!(bvh.top_level.bounds(), all);
+    }
+
+    #[test]
+    fn refit_reports_changed_nodes() {
+        let mut scene = test_scene();
+        let mut bvh = SceneBvh::build(&scene, &BvhBuildOptions::default());
+        let mesh_bounds = bvh.mesh_bounds();
+        let before = bvh.top_level.nodes.clone();
+
+        moved(&mut scene, 7, Vector3::new(0.0, 0.0, 0.25));
+        let changed = bvh
+            .top_level
+            .refit(|index| geometry_bounds(&scene.geometry[index as usize], &mesh_bounds))
+            .unwrap();
+
+        // The range is exactly from the first to the last node that changed
+        let differs: Vec<usize> = (0..before.len())
+            .filter(|&index| !same_node(&before[index], &bvh.top_level.nodes[index]))
+            .collect();
+        assert_eq!(changed, differs[0]..differs[differs.len() - 1] + 1);
+
+        // Refitting again with nothing moved changes nothing
+        let refit = bvh
+            .top_level
+            .refit(|index| geometry_bounds(&scene.geometry[index as usize], &mesh_bounds));
+        assert_eq!(refit, None);
+    }
+
+    // Spheres spaced out along x
+    fn row(count: usize) -> Vec<Geometry> {
+        (0..count)
+            .map(|i| {
+                let position = Vector3::new(i as f32 * 3.0, 0.0, 0.0);
+                Geometry::new(GeomType::SPHERE, Matrix4::from_translation(position), 0)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rebuilds_past_threshold() {
+        let mut geometry = row(64);
+        let options = BvhBuildOptions::default();
+        let bvh = Bvh::over_geometry(&geometry, &[], &options);
+        let built_cost = bvh.stats.sah_cost;
+
+        // Shuffling the row leaves every leaf spanning most of it
+        let transforms: Vec<_> = geometry.iter().map(|g| g.transf).collect();
+        for (index, geometry) in geometry.iter_mut().enumerate() {
+            geometry.set_transform(Matrix4::from(transforms[index * 27 % 64]));
+        }
+
+        let mut refit = bvh.clone();
+        let lenient = BvhBuildOptions {
+            rebuild_threshold: f32::INFINITY,
+            ..options
+        };
+        let update = refit.update_top_level(&geometry, &[], built_cost, &lenient);
+        assert!(matches!(update, TopLevelUpdate::Refit(Some(_))));
+        assert!(refit.stats.sah_cost > built_cost * options.rebuild_threshold);
+
+        let mut rebuilt = bvh.clone();
+        let update = rebuilt.update_top_level(&geometry, &[], built_cost, &options);
+        assert_eq!(update, TopLevelUpdate::Rebuilt);
+        assert!(rebuilt.stats.sah_cost <= built_cost * options.rebuild_threshold);
+        check_bounds(
+            &rebuilt,
+            |index| geometry_bounds(&geometry[index as usize], &[]),
+            options.max_leaf_size,
+        );
+    }
+
+    #[test]
+    fn top_level_capacity_fits_any_rebuild() {
+        let options = BvhBuildOptions {
+            max_leaf_size: 1,
+            ..BvhBuildOptions::default()
+        };
+        for &count in &[1, 2, 3, 7, 40] {
+            let bvh = Bvh::over_geometry(&row(count), &[], &options);
+            let (node_capacity, primitive_capacity) = top_level_capacity(count);
+            assert!(bvh.nodes.len() <= node_capacity);
+            assert!(bvh.primitives.len() <= primitive_capacity);
+        }
+    }
+
+    #[test]
+    fn flatten_pads_the_top_level() {
+        let scene = test_scene();
+        let bvh = SceneBvh::build(&scene, &BvhBuildOptions::default());
+        let flat = bvh.flatten();
+        let (node_capacity, primitive_capacity) = top_level_capacity(scene.geometry.len());
+
+        let top_level = &bvh.top_level;
+        for (index, node) in flat.nodes[..node_capacity].iter().enumerate() {
+            let expected = top_level.nodes.get(index).copied().unwrap_or_default();
+            assert!(same_node(node, &expected));
+        }
+        let mut primitives = top_level.primitives.clone();
+        primitives.resize(primitive_capacity, 0);
+        assert_eq!(flat.primitives[..primitive_capacity], primitives[..]);
+
+        // The mesh tree comes after the padding, with its offsets moved along with it
+        assert_eq!(flat.mesh_roots, vec![node_capacity as u32]);
+        let bottom_level = &bvh.bottom_level[0];
+        assert_eq!(flat.nodes.len(), node_capacity + bottom_level.nodes.len());
+        for (node, flat_node) in bottom_level.nodes.iter().zip(&flat.nodes[node_capacity..]) {
+            let base = if node.count > 0 {
+                primitive_capacity
+            } else {
+                node_capacity
+            };
+            assert_eq!(flat_node.offset, node.offset + base as u32);
+            assert_eq!(flat_node.count, node.count);
+        }
+        assert_eq!(
+            flat.primitives[primitive_capacity..],
+            bottom_level.primitives[..]
+        );
+    }
+
+    #[test]
+    fn flatten_empty_scene() {
+        let flat = SceneBvh::build(&Scene::empty(), &BvhBuildOptions::default()).flatten();
+        assert_eq!(flat.nodes.len(), 1);
+        assert!(is_empty_tree(&flat.nodes[0]));
+        assert_eq!(flat.primitives, vec![0]);
+        assert!(flat.mesh_roots.is_empty());
+    }
+}
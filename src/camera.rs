@@ -0,0 +1,270 @@
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, Matrix4, Point3, Vector3};
+use serde::Deserialize;
+
+/// How pixels map to ray directions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum Projection {
+    /// A regular pinhole or thin lens camera
+    #[default]
+    Perspective,
+    /// Parallel rays, covering what the perspective view does at the focus distance. Useful
+    /// for technical drawings.
+    Orthographic,
+    /// The whole sphere around the camera, longitude across and latitude down. Panoramas for
+    /// environment maps and VR previews want a 2:1 image.
+    Equirectangular,
+    /// Equidistant fisheye, where the angle off the view direction grows with the distance
+    /// from the center and reaches `fovy` at the top and bottom edges.
+    Fisheye,
+}
+
+impl Projection {
+    // In declaration order, to get one back from its index
+    const ALL: [Self; 4] = [
+        Projection::Perspective,
+        Projection::Orthographic,
+        Projection::Equirectangular,
+        Projection::Fisheye,
+    ];
+}
+
+impl std::str::FromStr for Projection {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "perspective" => Ok(Projection::Perspective),
+            "orthographic" => Ok(Projection::Orthographic),
+            "equirectangular" => Ok(Projection::Equirectangular),
+            "fisheye" => Ok(Projection::Fisheye),
+            _ => Err(format!("Unknown projection: {}", name)),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Camera {
+    resolution: [u32; 2],
+    pixel_length: [f32; 2],
+    position: [f32; 3],
+    aspect_ratio: f32,
+    at: [f32; 3], // Point the camera is facing
+    fovx: f32,
+    up: [f32; 3],
+    fovy: f32,
+    right: [f32; 3],
+    _padding: u32,
+    view_dir: [f32; 3], // Direction to camera from look_at
+    // Radius of the thin lens, 0 for a pinhole
+    aperture: f32,
+    // Distance along view_dir to the plane that's in focus
+    focus_distance: f32,
+    // A Projection as its index, matching the constants in generate_paths.comp
+    projection: u32,
+}
+
+impl Camera {
+    /// A camera at the origin looking down +z.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self::looking_at(
+            width,
+            height,
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 0.0, 1.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            45.0,
+        )
+    }
+
+    /// A pinhole camera at `position` facing `target`, with `fovy` in degrees. It's focused on
+    /// `target` once `with_thin_lens` gives it an aperture.
+    pub fn looking_at(
+        width: u32,
+        height: u32,
+        position: Point3<f32>,
+        target: Point3<f32>,
+        up: Vector3<f32>,
+        fovy: f32,
+    ) -> Self {
+        let resolution = [width, height];
+        let view = (target - position).normalize();
+
+        let r = up.cross(view).normalize();
+        let up = view.cross(r);
+
+        let aspect_ratio = resolution[0] as f32 / resolution[1] as f32;
+        let y_scaled = (fovy * PI / 180f32).tan();
+        let x_scaled = (y_scaled * width as f32) / height as f32;
+        let fovx = (x_scaled.atan() * 180f32) / PI;
+        let pixel_length: [f32; 2] = [
+            (2.0 * x_scaled) / width as f32,
+            (2.0 * y_scaled) / height as f32,
+        ];
+
+        Self {
+            resolution,
+            pixel_length,
+            position: position.into(),
+            aspect_ratio,
+            at: target.into(),
+            fovx,
+            up: up.into(),
+            fovy,
+            right: r.into(),
+            _padding: 0,
+            view_dir: view.into(),
+            aperture: 0.0,
+            focus_distance: (target - position).magnitude(),
+            projection: 0,
+        }
+    }
+
+    pub fn with_projection(mut self, projection: Projection) -> Self {
+        self.projection = projection as u32;
+        self
+    }
+
+    pub fn projection(&self) -> Projection {
+        Projection::ALL[self.projection as usize]
+    }
+
+    /// The same camera, lens and projection seen from somewhere else. Anything that moves the
+    /// camera should go through this so those stick.
+    pub fn with_view(
+        &self,
+        position: Point3<f32>,
+        target: Point3<f32>,
+        up: Vector3<f32>,
+        fovy: f32,
+    ) -> Self {
+        Self {
+            aperture: self.aperture,
+            focus_distance: self.focus_distance,
+            projection: self.projection,
+            ..Self::looking_at(self.res_x(), self.res_y(), position, target, up, fovy)
+        }
+    }
+
+    /// Turns the pinhole into a thin lens of radius `aperture`, with everything
+    /// `focus_distance` in front of the camera sharp. The larger the aperture, the blurrier
+    /// everything else gets.
+    pub fn with_thin_lens(mut self, aperture: f32, focus_distance: f32) -> Self {
+        self.aperture = aperture;
+        self.focus_distance = focus_distance;
+        self
+    }
+
+    pub fn set_focus_distance(&mut self, focus_distance: f32) {
+        self.focus_distance = focus_distance;
+    }
+
+    pub fn aperture(&self) -> f32 {
+        self.aperture
+    }
+
+    pub fn focus_distance(&self) -> f32 {
+        self.focus_distance
+    }
+
+    /// The same view at another resolution. The vertical field of view is kept, so a wider
+    /// image sees more to the sides instead of being stretched.
+    pub fn with_resolution(&self, width: u32, height: u32) -> Self {
+        Self {
+            resolution: [width, height],
+            ..*self
+        }
+        .with_view(
+            self.position(),
+            Point3::from(self.at),
+            Vector3::from(self.up),
+            self.fovy,
+        )
+    }
+
+    /// The ray `generate_paths.comp` starts through pixel (`x`, `y`) when the lens is a
+    /// pinhole, for picking what's under the cursor. None where a fisheye doesn't see.
+    pub fn primary_ray(&self, x: f32, y: f32) -> Option<(Point3<f32>, Vector3<f32>)> {
+        let offset_x = x - self.resolution[0] as f32 * 0.5;
+        let offset_y = y - self.resolution[1] as f32 * 0.5;
+        let view = Vector3::from(self.view_dir);
+        let right = Vector3::from(self.right);
+        let up = Vector3::from(self.up);
+
+        // `right` points against the pixel x axis
+        let ray = match self.projection() {
+            Projection::Perspective => {
+                let direction = view
+                    - right * self.pixel_length[0] * offset_x
+                    - up * self.pixel_length[1] * offset_y;
+                (self.position(), direction.normalize())
+            }
+            Projection::Orthographic => {
+                let offset =
+                    right * self.pixel_length[0] * offset_x + up * self.pixel_length[1] * offset_y;
+                (self.position() - offset * self.focus_distance, view)
+            }
+            Projection::Equirectangular => {
+                let phi = 2.0 * PI * offset_x / self.resolution[0] as f32;
+                let theta = -PI * offset_y / self.resolution[1] as f32;
+                let direction =
+                    (view * phi.cos() - right * phi.sin()) * theta.cos() + up * theta.sin();
+                (self.position(), direction)
+            }
+            Projection::Fisheye => {
+                let half_height = self.resolution[1] as f32 * 0.5;
+                let (px, py) = (offset_x / half_height, offset_y / half_height);
+                let r = (px * px + py * py).sqrt();
+                let angle = r * self.fovy.to_radians();
+                if angle > PI {
+                    return None;
+                }
+                let (dx, dy) = if r > 0.0 {
+                    (px / r, py / r)
+                } else {
+                    (0.0, 0.0)
+                };
+                let direction = view * angle.cos() - (right * dx + up * dy) * angle.sin();
+                (self.position(), direction)
+            }
+        };
+        Some(ray)
+    }
+
+    pub fn look_at(&self) -> Matrix4<f32> {
+        Matrix4::look_at_rh(
+            Point3::from(self.position),
+            Point3::from(self.at),
+            Vector3::from(self.up),
+        )
+    }
+
+    pub fn perspective(&self) -> Matrix4<f32> {
+        let fovy: cgmath::Rad<f32> = cgmath::Deg(self.fovy).into();
+        cgmath::perspective(fovy, self.aspect_ratio, 0.1, 10.0)
+    }
+
+    pub fn position(&self) -> Point3<f32> {
+        Point3::from(self.position)
+    }
+
+    /// Unit vector the camera looks along.
+    pub fn direction(&self) -> Vector3<f32> {
+        Vector3::from(self.view_dir)
+    }
+
+    /// Half the vertical field of view, in degrees.
+    pub fn fovy(&self) -> f32 {
+        self.fovy
+    }
+
+    pub fn res_x(&self) -> u32 {
+        self.resolution[0]
+    }
+
+    pub fn res_y(&self) -> u32 {
+        self.resolution[1]
+    }
+}
@@ -0,0 +1,158 @@
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+use oscuras::bvh::{BvhBuildOptions, SplitMethod};
+use oscuras::scene_file::{SceneFile, SettingsDescription};
+use oscuras::{Projection, RenderSettings, Sampler};
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "oscuras", about = "A pathtracer written in Rust using WebGPU")]
+pub struct Cli {
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Open the interactive viewer (the default when no subcommand is given)
+    View(ViewOptions),
+    /// Render offline without a window and write the image to disk
+    Render(RenderOptions),
+}
+
+/// Options shared by every subcommand.
+#[derive(Debug, StructOpt)]
+pub struct CommonOptions {
+    /// Scene description file to load instead of the built-in scene
+    #[structopt(short, long, parse(from_os_str))]
+    pub scene: Option<PathBuf>,
+
+    /// Image width in pixels [default: 800, or the scene file's]
+    #[structopt(long)]
+    pub width: Option<u32>,
+
+    /// Image height in pixels [default: 600, or the scene file's]
+    #[structopt(long)]
+    pub height: Option<u32>,
+
+    /// Maximum number of bounces per path [default: 8, or the scene file's]
+    #[structopt(short = "b", long)]
+    pub max_bounces: Option<u32>,
+
+    /// Seed for the random numbers, the same seed renders the same image [default: 0, or the
+    /// scene file's]
+    #[structopt(long)]
+    pub seed: Option<u32>,
+
+    /// Where samples come from: random, sobol, halton or blue-noise [default: random, or the
+    /// scene file's]
+    #[structopt(long)]
+    pub sampler: Option<Sampler>,
+
+    /// Camera projection: perspective, orthographic, equirectangular or fisheye [default:
+    /// perspective, or the scene file's]
+    #[structopt(long)]
+    pub projection: Option<Projection>,
+
+    /// How to split BVH nodes: sah or median
+    #[structopt(long = "bvh", default_value = "sah")]
+    pub bvh_split: SplitMethod,
+
+    /// Most primitives a BVH leaf can hold before it has to be split
+    #[structopt(long, default_value = "4")]
+    pub leaf_size: usize,
+
+    /// Graphics backend: primary, secondary, vulkan, metal, dx12, dx11, gl or all
+    #[structopt(long, default_value = "primary", parse(try_from_str = parse_backend))]
+    pub backend: wgpu::BackendBit,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ViewOptions {
+    #[structopt(flatten)]
+    pub common: CommonOptions,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct RenderOptions {
+    #[structopt(flatten)]
+    pub common: CommonOptions,
+
+    /// Samples per pixel [default: 1, or the scene file's]
+    #[structopt(short = "n", long)]
+    pub samples: Option<u32>,
+
+    /// Output image, the format is picked from the extension
+    #[structopt(short, long, parse(from_os_str), default_value = "render.png")]
+    pub output: PathBuf,
+
+    /// Print how the BVH turned out, to compare builders
+    #[structopt(long)]
+    pub bvh_stats: bool,
+}
+
+impl Default for ViewOptions {
+    fn default() -> Self {
+        ViewOptions::from_iter(&["view"])
+    }
+}
+
+// Flags win over the scene file, which wins over these
+const DEFAULT_WIDTH: u32 = 800;
+const DEFAULT_HEIGHT: u32 = 600;
+
+impl CommonOptions {
+    /// Loads the scene file if one was given, the built-in scene otherwise.
+    pub fn scene_file(&self) -> anyhow::Result<SceneFile> {
+        match &self.scene {
+            Some(path) => SceneFile::load(path),
+            None => Ok(SceneFile::default()),
+        }
+    }
+
+    pub fn size(&self, file: &SettingsDescription) -> (u32, u32) {
+        (
+            self.width.or(file.width).unwrap_or(DEFAULT_WIDTH),
+            self.height.or(file.height).unwrap_or(DEFAULT_HEIGHT),
+        )
+    }
+
+    pub fn settings(
+        &self,
+        file: &SettingsDescription,
+        samples_per_pixel: Option<u32>,
+    ) -> RenderSettings {
+        let defaults = RenderSettings::default();
+        RenderSettings {
+            samples_per_pixel: samples_per_pixel
+                .or(file.samples_per_pixel)
+                .unwrap_or(defaults.samples_per_pixel),
+            max_bounces: self
+                .max_bounces
+                .or(file.max_bounces)
+                .unwrap_or(defaults.max_bounces),
+            seed: self.seed.or(file.seed).unwrap_or(defaults.seed),
+            sampler: self.sampler.or(file.sampler).unwrap_or(defaults.sampler),
+            bvh: BvhBuildOptions {
+                split: self.bvh_split,
+                max_leaf_size: self.leaf_size,
+                ..BvhBuildOptions::default()
+            },
+        }
+    }
+}
+
+fn parse_backend(name: &str) -> Result<wgpu::BackendBit, String> {
+    match name.to_lowercase().as_str() {
+        "primary" => Ok(wgpu::BackendBit::PRIMARY),
+        "secondary" => Ok(wgpu::BackendBit::SECONDARY),
+        "all" => Ok(wgpu::BackendBit::all()),
+        "vulkan" => Ok(wgpu::BackendBit::VULKAN),
+        "metal" => Ok(wgpu::BackendBit::METAL),
+        "dx12" => Ok(wgpu::BackendBit::DX12),
+        "dx11" => Ok(wgpu::BackendBit::DX11),
+        "gl" => Ok(wgpu::BackendBit::GL),
+        _ => Err(format!("Unknown backend: {}", name)),
+    }
+}
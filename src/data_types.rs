@@ -0,0 +1,254 @@
+use bitflags;
+use bytemuck::Zeroable;
+use cgmath::{Matrix, Matrix4, Transform};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Intersection {
+    surface_normal: [f32; 3],
+    t: f32,
+    uv: [f32; 2],
+    material_id: u32,
+    _padding: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Ray {
+    origin: [f32; 3],
+    _padding: u32,
+    direction: [f32; 3],
+    _more_padding: u32,
+}
+
+/// One path in flight. Paths stay in their pixel's slot for the whole wavefront, a path with
+/// no bounces left is done and only its radiance is read back.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PathSegment {
+    ray: Ray,
+    throughput: [f32; 3],
+    pixel_index: u32,
+    radiance: [f32; 3],
+    remaining_bounces: i32,
+}
+
+/// Flattened BVH node, stored depth first so the left child always follows its parent.
+/// Leaves have a non-zero `count` and `offset` is their first primitive, interior nodes keep
+/// the index of their right child in `offset`. That is never 0, so an all zero root means
+/// the tree is empty.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BvhNode {
+    pub min: [f32; 3],
+    pub offset: u32,
+    pub max: [f32; 3],
+    pub count: u32,
+}
+
+bitflags::bitflags! {
+    #[repr(transparent)]
+    #[derive(bytemuck::Pod, bytemuck::Zeroable)]
+    pub struct GeomType: u32 {
+        const SPHERE = 1;
+        const BOX = 2;
+        const TRIANGLE = 4;
+    }
+}
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Geometry {
+    pub transf: [[f32; 4]; 4],
+    pub inverse: [[f32; 4]; 4],
+    pub transp_inv: [[f32; 4]; 4],
+    pub ty: GeomType,
+    pub material_id: u32,
+    /// Only meaningful for `GeomType::TRIANGLE`
+    pub mesh_id: u32,
+    _padding: u32,
+}
+
+/// Mesh vertex as the hit shader reads it. A zero normal means the mesh has none and the
+/// face normal is used instead.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    _padding: u32,
+    pub normal: [f32; 3],
+    _more_padding: u32,
+    pub uv: [f32; 2],
+    _even_more_padding: [u32; 2],
+}
+
+/// Where a mesh lives in the shared vertex, index and BVH node buffers.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MeshInfo {
+    pub first_index: u32,
+    pub index_count: u32,
+    pub base_vertex: u32,
+    /// Root node of the mesh's bottom level BVH
+    pub bvh_root: u32,
+}
+
+/// Where a texture lives in the shared texel buffer.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TextureInfo {
+    pub offset: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+bitflags::bitflags! {
+    #[repr(transparent)]
+    #[derive(bytemuck::Pod, bytemuck::Zeroable)]
+    pub struct MaterialType: u32 {
+        const LAMBERTIAN = 1;
+        const METAL = 2;
+        const DIELECTRIC = 4;
+        const EMISSIVE = 8;
+    }
+}
+
+/// Surface description shared by every geometry that points at it through `material_id`.
+/// Emission is added for every material type, but only emissive ones stop the path.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Material {
+    pub albedo: [f32; 3],
+    pub ty: MaterialType,
+    pub emission: [f32; 3],
+    /// Fuzz added to metal reflections, 0 is a perfect mirror
+    pub roughness: f32,
+    /// Index of refraction for dielectrics
+    pub ior: f32,
+    /// Texture multiplied into the albedo, -1 for none
+    pub texture_id: i32,
+    _padding: [u32; 2],
+}
+
+impl Geometry {
+    /// Builds a geometry from its object-to-world transform, caching the inverse and
+    /// inverse-transpose the shaders need.
+    pub fn new(ty: GeomType, transform: Matrix4<f32>, material_id: u32) -> Self {
+        let mut geometry = Self {
+            ty,
+            material_id,
+            ..Self::zeroed()
+        };
+        geometry.set_transform(transform);
+        geometry
+    }
+
+    /// Moves the geometry, recomputing the cached matrices. Use `Scene::set_transform` on
+    /// geometry that is already being rendered so the change reaches the GPU.
+    pub fn set_transform(&mut self, transform: Matrix4<f32>) {
+        let inverse = transform
+            .inverse_transform()
+            .expect("Geometry transform is not invertible");
+        self.transf = transform.into();
+        self.inverse = inverse.into();
+        self.transp_inv = inverse.transpose().into();
+    }
+
+    /// Places a mesh added with `Scene::add_mesh` in the world.
+    pub fn mesh(mesh_id: u32, transform: Matrix4<f32>, material_id: u32) -> Self {
+        Self {
+            mesh_id,
+            ..Self::new(GeomType::TRIANGLE, transform, material_id)
+        }
+    }
+}
+
+impl MeshVertex {
+    pub fn new(position: [f32; 3], normal: [f32; 3], uv: [f32; 2]) -> Self {
+        Self {
+            position,
+            normal,
+            uv,
+            ..Self::default()
+        }
+    }
+}
+
+impl MeshInfo {
+    pub fn new(first_index: u32, index_count: u32, base_vertex: u32, bvh_root: u32) -> Self {
+        Self {
+            first_index,
+            index_count,
+            base_vertex,
+            bvh_root,
+        }
+    }
+}
+
+impl TextureInfo {
+    pub fn new(offset: u32, width: u32, height: u32) -> Self {
+        Self {
+            offset,
+            width,
+            height,
+        }
+    }
+}
+
+impl Material {
+    pub fn lambertian(albedo: [f32; 3]) -> Self {
+        Self {
+            albedo,
+            ty: MaterialType::LAMBERTIAN,
+            ..Self::default()
+        }
+    }
+
+    pub fn textured(texture_id: u32) -> Self {
+        Self {
+            albedo: [1.0; 3],
+            texture_id: texture_id as i32,
+            ..Self::default()
+        }
+    }
+
+    pub fn metal(albedo: [f32; 3], roughness: f32) -> Self {
+        Self {
+            albedo,
+            ty: MaterialType::METAL,
+            roughness,
+            ..Self::default()
+        }
+    }
+
+    pub fn dielectric(ior: f32) -> Self {
+        Self {
+            albedo: [1.0; 3],
+            ty: MaterialType::DIELECTRIC,
+            ior,
+            ..Self::default()
+        }
+    }
+
+    pub fn emissive(emission: [f32; 3]) -> Self {
+        Self {
+            albedo: [0.0; 3],
+            ty: MaterialType::EMISSIVE,
+            emission,
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            albedo: [0.5; 3],
+            ty: MaterialType::LAMBERTIAN,
+            emission: [0.0; 3],
+            roughness: 0.0,
+            ior: 1.0,
+            texture_id: -1,
+            _padding: [0; 2],
+        }
+    }
+}
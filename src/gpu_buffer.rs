@@ -56,6 +56,10 @@ impl GPUBuffer {
         self.handle.slice(..)
     }
 
+    pub fn handle(&self) -> &wgpu::Buffer {
+        &self.handle
+    }
+
     pub fn as_bgl_entry(
         &self,
         binding: u32,
@@ -0,0 +1,75 @@
+use std::path::Path;
+
+use oscuras::bvh::SceneBvhStats;
+use oscuras::{Camera, Pathtracer, RenderSettings, Scene};
+
+/// Renders without a window so we can run on build machines and batch jobs.
+pub struct Headless {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pathtracer: Pathtracer,
+}
+
+impl Headless {
+    pub async fn new(
+        backend: wgpu::BackendBit,
+        scene: &Scene,
+        camera: &Camera,
+        settings: RenderSettings,
+    ) -> Self {
+        // No surface to be compatible with, so any adapter will do (including software ones)
+        let instance = wgpu::Instance::new(backend);
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: None,
+            })
+            .await
+            .expect("No suitable adapter found");
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: Some("headless_device"),
+                    features: wgpu::Features::empty(),
+                    limits: Pathtracer::required_limits(),
+                },
+                None,
+            )
+            .await
+            .unwrap();
+
+        let pathtracer = Pathtracer::new(&device, camera, scene, settings);
+
+        Self {
+            device,
+            queue,
+            pathtracer,
+        }
+    }
+
+    /// Runs the pathtracer once per sample in the render settings.
+    pub fn render(&mut self) {
+        for _ in 0..self.pathtracer.settings().samples_per_pixel {
+            let mut encoder = self
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Headless Encoder"),
+                });
+            self.pathtracer.run(&self.queue, &mut encoder);
+            self.queue.submit(std::iter::once(encoder.finish()));
+        }
+    }
+
+    pub fn bvh_stats(&self) -> &SceneBvhStats {
+        self.pathtracer.bvh_stats()
+    }
+
+    /// Reads back the rendered image and writes it to `path`, the format is picked from the
+    /// extension.
+    pub fn save(&self, path: &Path) -> image::ImageResult<()> {
+        self.pathtracer
+            .read_image(&self.device, &self.queue)
+            .save(path)
+    }
+}
@@ -0,0 +1,4 @@
+//! Loaders that bring assets from other tools into a [`Scene`](crate::Scene).
+
+pub mod gltf;
+pub mod obj;
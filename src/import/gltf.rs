@@ -0,0 +1,480 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use cgmath::{EuclideanSpace, Matrix4, Point3, SquareMatrix, Transform, Vector3};
+
+use crate::camera::Camera;
+use crate::data_types::{Geometry, Material, MeshVertex};
+use crate::mesh::Mesh;
+use crate::scene::Scene;
+use crate::texture::Texture;
+
+/// What a glTF file added to the scene.
+#[derive(Debug, Clone)]
+pub struct Loaded {
+    /// Indices of the geometry that was added
+    pub geometry: Vec<usize>,
+    /// The first perspective camera found in the node hierarchy
+    pub camera: Option<ImportedCamera>,
+}
+
+/// Camera placement from the file. The resolution is up to the caller, so this only becomes
+/// a `Camera` once it is known.
+#[derive(Debug, Clone, Copy)]
+pub struct ImportedCamera {
+    pub position: Point3<f32>,
+    pub target: Point3<f32>,
+    pub up: Vector3<f32>,
+    /// Half of the vertical field of view in degrees, like `Camera::looking_at` takes it
+    pub fovy: f32,
+}
+
+impl ImportedCamera {
+    pub fn camera(&self, width: u32, height: u32) -> Camera {
+        Camera::looking_at(
+            width,
+            height,
+            self.position,
+            self.target,
+            self.up,
+            self.fovy,
+        )
+    }
+}
+
+/// Loads the default scene of a `.gltf` or `.glb` file into `scene`, with `transform` applied
+/// on top of the node hierarchy.
+///
+/// Every primitive becomes a mesh, and a mesh used by several nodes is only uploaded once.
+/// PBR materials are mapped onto the closest material we support:
+/// - a non-zero emissive factor makes it emissive
+/// - blended materials with some transparency become glass
+/// - mostly metallic materials become metal with the base color and roughness factor
+/// - everything else is diffuse with the base color factor
+///
+/// Metal and diffuse materials keep the base color texture, unless its pixel format isn't one
+/// we can convert. Metallic-roughness textures aren't supported, those materials only use the
+/// factors.
+pub fn load(scene: &mut Scene, path: &Path, transform: Matrix4<f32>) -> Result<Loaded> {
+    let (document, buffers, images) =
+        gltf::import(path).with_context(|| format!("Unable to load {}", path.display()))?;
+
+    let gltf_scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| anyhow!("{} has no scenes", path.display()))?;
+
+    let mut loader = Loader {
+        scene,
+        buffers: &buffers,
+        images: &images,
+        materials: HashMap::new(),
+        textures: HashMap::new(),
+        meshes: HashMap::new(),
+        default_material: None,
+        loaded: Loaded {
+            geometry: Vec::new(),
+            camera: None,
+        },
+    };
+    for node in gltf_scene.nodes() {
+        loader.load_node(&node, transform)?;
+    }
+
+    Ok(loader.loaded)
+}
+
+struct Loader<'a> {
+    scene: &'a mut Scene,
+    buffers: &'a [gltf::buffer::Data],
+    images: &'a [gltf::image::Data],
+    // glTF indices to the ones in our scene
+    materials: HashMap<usize, u32>,
+    // Images that couldn't be converted are kept as None so they only warn once
+    textures: HashMap<usize, Option<u32>>,
+    // Every primitive of a glTF mesh as (mesh_id, material_id)
+    meshes: HashMap<usize, Vec<(u32, u32)>>,
+    default_material: Option<u32>,
+    loaded: Loaded,
+}
+
+impl Loader<'_> {
+    fn load_node(&mut self, node: &gltf::Node, parent: Matrix4<f32>) -> Result<()> {
+        let world = parent * Matrix4::from(node.transform().matrix());
+
+        if let Some(camera) = node.camera() {
+            self.load_camera(&camera, world);
+        }
+
+        if let Some(mesh) = node.mesh() {
+            // Zero scales are a common way to hide things, but we can't invert them
+            if world.invert().is_some() {
+                for (mesh_id, material_id) in self.load_mesh(&mesh)? {
+                    let geometry = Geometry::mesh(mesh_id, world, material_id);
+                    self.loaded.geometry.push(self.scene.add_geometry(geometry));
+                }
+            } else {
+                log::warn!("Skipping node {:?}, its transform is singular", node.name());
+            }
+        }
+
+        for child in node.children() {
+            self.load_node(&child, world)?;
+        }
+        Ok(())
+    }
+
+    // glTF cameras look down -z with +y up
+    fn load_camera(&mut self, camera: &gltf::Camera, world: Matrix4<f32>) {
+        if self.loaded.camera.is_some() {
+            return;
+        }
+        match camera.projection() {
+            gltf::camera::Projection::Perspective(perspective) => {
+                let position = world.transform_point(Point3::origin());
+                let forward = world.transform_vector(Vector3::new(0.0, 0.0, -1.0));
+                self.loaded.camera = Some(ImportedCamera {
+                    position,
+                    target: position + forward,
+                    up: world.transform_vector(Vector3::unit_y()),
+                    fovy: perspective.yfov().to_degrees() / 2.0,
+                });
+            }
+            gltf::camera::Projection::Orthographic(_) => {
+                log::warn!("Ignoring orthographic camera {:?}", camera.name());
+            }
+        }
+    }
+
+    fn load_mesh(&mut self, mesh: &gltf::Mesh) -> Result<Vec<(u32, u32)>> {
+        if let Some(primitives) = self.meshes.get(&mesh.index()) {
+            return Ok(primitives.clone());
+        }
+
+        let mut primitives = Vec::new();
+        for primitive in mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                log::warn!(
+                    "Skipping {:?} primitive in mesh {:?}",
+                    primitive.mode(),
+                    mesh.name()
+                );
+                continue;
+            }
+
+            let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+            let positions: Vec<[f32; 3]> = match reader.read_positions() {
+                Some(positions) => positions.collect(),
+                None => continue,
+            };
+            // Missing normals stay zeroed so the hit shader falls back to the face normal
+            let normals: Vec<[f32; 3]> = reader
+                .read_normals()
+                .map(|normals| normals.collect())
+                .unwrap_or_else(|| vec![[0.0; 3]; positions.len()]);
+            let uvs: Vec<[f32; 2]> = reader
+                .read_tex_coords(0)
+                .map(|uvs| uvs.into_f32().collect())
+                .unwrap_or_else(|| vec![[0.0; 2]; positions.len()]);
+            let indices: Vec<u32> = reader
+                .read_indices()
+                .map(|indices| indices.into_u32().collect())
+                .unwrap_or_else(|| (0..positions.len() as u32).collect());
+
+            let vertices = positions
+                .iter()
+                .zip(&normals)
+                .zip(&uvs)
+                .map(|((&position, &normal), &uv)| MeshVertex::new(position, normal, uv))
+                .collect();
+
+            let mesh_id = self.scene.add_mesh(Mesh::new(vertices, indices));
+            let material_id = self.load_material(&primitive.material());
+            primitives.push((mesh_id, material_id));
+        }
+
+        self.meshes.insert(mesh.index(), primitives.clone());
+        Ok(primitives)
+    }
+
+    fn load_material(&mut self, material: &gltf::Material) -> u32 {
+        // Primitives without a material use the spec's default, which has no index
+        let index = match material.index() {
+            Some(index) => index,
+            None => {
+                let scene = &mut *self.scene;
+                return *self
+                    .default_material
+                    .get_or_insert_with(|| scene.add_material(Material::default()));
+            }
+        };
+        if let Some(&id) = self.materials.get(&index) {
+            return id;
+        }
+
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, alpha] = pbr.base_color_factor();
+        let emission = material.emissive_factor();
+
+        let converted = if emission.iter().any(|&c| c > 0.0) {
+            Material::emissive(emission)
+        } else if material.alpha_mode() == gltf::material::AlphaMode::Blend && alpha < 1.0 {
+            Material::dielectric(1.5)
+        } else {
+            let mut converted = if pbr.metallic_factor() >= 0.5 {
+                Material::metal([r, g, b], pbr.roughness_factor())
+            } else {
+                Material::lambertian([r, g, b])
+            };
+            if let Some(info) = pbr.base_color_texture() {
+                if info.tex_coord() == 0 {
+                    if let Some(id) = self.load_texture(&info.texture()) {
+                        converted.texture_id = id as i32;
+                    }
+                }
+            }
+            converted
+        };
+        if pbr.metallic_roughness_texture().is_some() {
+            log::warn!(
+                "Ignoring the metallic-roughness texture of material {:?}, using its factors",
+                material.name()
+            );
+        }
+
+        let id = self.scene.add_material(converted);
+        self.materials.insert(index, id);
+        id
+    }
+
+    /// Like a broken texture in an MTL file, an image we can't convert only loses the detail.
+    fn load_texture(&mut self, texture: &gltf::Texture) -> Option<u32> {
+        let image = texture.source();
+        if let Some(&id) = self.textures.get(&image.index()) {
+            return id;
+        }
+
+        let id = match convert_image(&self.images[image.index()]) {
+            Ok(converted) => Some(self.scene.add_texture(converted)),
+            Err(e) => {
+                log::warn!(
+                    "Dropping image {}, materials using it keep their base color factor: {:#}",
+                    image.index(),
+                    e
+                );
+                None
+            }
+        };
+        self.textures.insert(image.index(), id);
+        id
+    }
+}
+
+fn convert_image(data: &gltf::image::Data) -> Result<Texture> {
+    use gltf::image::Format;
+
+    let rgba: Vec<u8> = match data.format {
+        Format::R8 => data
+            .pixels
+            .iter()
+            .flat_map(|&v| vec![v, v, v, 255])
+            .collect(),
+        Format::R8G8 => data
+            .pixels
+            .chunks_exact(2)
+            .flat_map(|p| vec![p[0], p[1], 0, 255])
+            .collect(),
+        Format::R8G8B8 => data
+            .pixels
+            .chunks_exact(3)
+            .flat_map(|p| vec![p[0], p[1], p[2], 255])
+            .collect(),
+        Format::R8G8B8A8 => data.pixels.clone(),
+        Format::B8G8R8 => data
+            .pixels
+            .chunks_exact(3)
+            .flat_map(|p| vec![p[2], p[1], p[0], 255])
+            .collect(),
+        Format::B8G8R8A8 => data
+            .pixels
+            .chunks_exact(4)
+            .flat_map(|p| vec![p[2], p[1], p[0], p[3]])
+            .collect(),
+        format => return Err(anyhow!("Unsupported texture format {:?}", format)),
+    };
+
+    let image = image::RgbaImage::from_raw(data.width, data.height, rgba)
+        .ok_or_else(|| anyhow!("Texture data doesn't match its size"))?;
+    Ok(Texture::from_image(&image))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::MaterialType;
+    use cgmath::InnerSpace;
+    use std::fs;
+
+    // One triangle with texture coordinates, placed by a small node hierarchy. The images are
+    // a red 8-bit RGBA pixel and a 16-bit RGB one we can't convert.
+    const FIXTURE: &str = r#"{
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0, 3, 4, 5, 6, 7, 8] }],
+        "nodes": [
+            { "name": "parent", "translation": [0, 1, 0], "scale": [2, 2, 2], "children": [1, 2] },
+            { "name": "child", "translation": [1, 0, 0], "mesh": 0 },
+            { "name": "sibling", "translation": [0, 0, 1], "mesh": 0 },
+            { "name": "camera", "translation": [0, 0, 5], "rotation": [0, 0.70710677, 0, 0.70710677], "camera": 0 },
+            { "mesh": 1 },
+            { "mesh": 2 },
+            { "mesh": 3 },
+            { "mesh": 4 },
+            { "mesh": 5 }
+        ],
+        "cameras": [{ "type": "perspective", "perspective": { "yfov": 0.8, "znear": 0.1 } }],
+        "meshes": [
+            { "primitives": [{ "attributes": { "POSITION": 0, "TEXCOORD_0": 1 }, "material": 0 }] },
+            { "primitives": [{ "attributes": { "POSITION": 0, "TEXCOORD_0": 1 }, "material": 1 }] },
+            { "primitives": [{ "attributes": { "POSITION": 0, "TEXCOORD_0": 1 }, "material": 2 }] },
+            { "primitives": [{ "attributes": { "POSITION": 0, "TEXCOORD_0": 1 }, "material": 3 }] },
+            { "primitives": [{ "attributes": { "POSITION": 0, "TEXCOORD_0": 1 }, "material": 4 }] },
+            { "primitives": [{ "attributes": { "POSITION": 0, "TEXCOORD_0": 1 }, "material": 5 }] }
+        ],
+        "materials": [
+            { "name": "clay", "pbrMetallicRoughness": { "baseColorFactor": [0.8, 0.2, 0.1, 1], "metallicFactor": 0 } },
+            { "name": "glass", "alphaMode": "BLEND", "pbrMetallicRoughness": { "baseColorFactor": [1, 1, 1, 0.5] } },
+            { "name": "light", "emissiveFactor": [1, 2, 3] },
+            { "name": "gold", "pbrMetallicRoughness": { "baseColorFactor": [0.9, 0.8, 0.7, 1], "metallicFactor": 0.5, "roughnessFactor": 0.25, "baseColorTexture": { "index": 0 } } },
+            { "name": "steel", "pbrMetallicRoughness": { "baseColorFactor": [0.6, 0.6, 0.6, 1], "roughnessFactor": 0.5, "baseColorTexture": { "index": 1 } } },
+            { "name": "brick", "pbrMetallicRoughness": { "metallicFactor": 0.2, "baseColorTexture": { "index": 0 } } }
+        ],
+        "textures": [{ "source": 0 }, { "source": 1 }],
+        "images": [
+            { "uri": "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR4nGP4z8DwHwAFAAH/iZk9HQAAAABJRU5ErkJggg==" },
+            { "uri": "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABEAIAAADA54+dAAAADUlEQVR4nGP4/58BCAAK/AH/E8mFCQAAAABJRU5ErkJggg==" }
+        ],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0] },
+            { "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC2" }
+        ],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 24 }
+        ],
+        "buffers": [{
+            "byteLength": 60,
+            "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAAAAAAAAAAAAIA/AAAAAAAAAAAAAIA/"
+        }]
+    }"#;
+
+    /// Writes the fixture into its own directory per test, so they can run in parallel.
+    fn load_fixture(name: &str) -> (Scene, Loaded) {
+        let dir =
+            std::env::temp_dir().join(format!("oscuras-gltf-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("scene.gltf");
+        fs::write(&path, FIXTURE).unwrap();
+
+        let mut scene = Scene::empty();
+        let transform = Matrix4::from_translation(Vector3::new(10.0, 0.0, 0.0));
+        let loaded = load(&mut scene, &path, transform).unwrap();
+        fs::remove_dir_all(dir).unwrap();
+        (scene, loaded)
+    }
+
+    fn material_of(scene: &Scene, geometry: usize) -> Material {
+        scene.materials[scene.geometry[geometry].material_id as usize]
+    }
+
+    fn assert_close(a: Point3<f32>, b: Point3<f32>) {
+        assert!((a - b).magnitude() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn node_hierarchy() {
+        let (scene, loaded) = load_fixture("node_hierarchy");
+        assert_eq!(loaded.geometry, (0..7).collect::<Vec<_>>());
+
+        // Parent scale and translation apply to the children, the load transform on top
+        let origin_of = |geometry: usize| {
+            Matrix4::from(scene.geometry[geometry].transf).transform_point(Point3::origin())
+        };
+        assert_close(origin_of(0), Point3::new(12.0, 1.0, 0.0));
+        assert_close(origin_of(1), Point3::new(10.0, 1.0, 2.0));
+        assert_close(origin_of(2), Point3::new(10.0, 0.0, 0.0));
+
+        let corner =
+            Matrix4::from(scene.geometry[0].transf).transform_point(Point3::new(1.0, 0.0, 0.0));
+        assert_close(corner, Point3::new(14.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn meshes_are_shared() {
+        let (scene, _) = load_fixture("meshes_are_shared");
+
+        // Two nodes place mesh 0, every other mesh is used once
+        assert_eq!(scene.meshes.len(), 6);
+        assert_eq!(scene.geometry[0].mesh_id, scene.geometry[1].mesh_id);
+        assert_eq!(scene.geometry[0].material_id, scene.geometry[1].material_id);
+
+        let mesh = &scene.meshes[scene.geometry[0].mesh_id as usize];
+        assert_eq!(mesh.indices, vec![0, 1, 2]);
+        assert_eq!(mesh.vertices[2].position, [0.0, 1.0, 0.0]);
+        assert_eq!(mesh.vertices[2].uv, [0.0, 1.0]);
+        // No normals in the file, the hit shader uses the face normal
+        assert!(mesh.vertices.iter().all(|v| v.normal == [0.0; 3]));
+    }
+
+    #[test]
+    fn materials() {
+        let (scene, _) = load_fixture("materials");
+
+        let clay = material_of(&scene, 0);
+        assert_eq!(clay.ty, MaterialType::LAMBERTIAN);
+        assert_eq!(clay.albedo, [0.8, 0.2, 0.1]);
+        assert_eq!(clay.texture_id, -1);
+
+        let glass = material_of(&scene, 2);
+        assert_eq!(glass.ty, MaterialType::DIELECTRIC);
+        assert_eq!(glass.ior, 1.5);
+
+        let light = material_of(&scene, 3);
+        assert_eq!(light.ty, MaterialType::EMISSIVE);
+        assert_eq!(light.emission, [1.0, 2.0, 3.0]);
+
+        // Metals keep their base color texture
+        let gold = material_of(&scene, 4);
+        assert_eq!(gold.ty, MaterialType::METAL);
+        assert_eq!(gold.albedo, [0.9, 0.8, 0.7]);
+        assert_eq!(gold.roughness, 0.25);
+        assert_eq!(gold.texture_id, 0);
+
+        // A 16-bit image can't be converted, only the factors are left
+        let steel = material_of(&scene, 5);
+        assert_eq!(steel.ty, MaterialType::METAL);
+        assert_eq!(steel.albedo, [0.6, 0.6, 0.6]);
+        assert_eq!(steel.texture_id, -1);
+
+        let brick = material_of(&scene, 6);
+        assert_eq!(brick.ty, MaterialType::LAMBERTIAN);
+        assert_eq!(brick.albedo, [1.0; 3]);
+        assert_eq!(brick.texture_id, 0);
+
+        assert_eq!(scene.textures.len(), 1);
+        assert_eq!(scene.textures[0].texels, vec![0xff00_00ff]);
+    }
+
+    #[test]
+    fn camera() {
+        let (_, loaded) = load_fixture("camera");
+        let camera = loaded.camera.unwrap();
+
+        // Turned a quarter to the left, so it looks down -x
+        assert_close(camera.position, Point3::new(10.0, 0.0, 5.0));
+        assert_close(camera.target, Point3::new(9.0, 0.0, 5.0));
+        assert!((camera.up - Vector3::unit_y()).magnitude() < 1e-5);
+        assert!((camera.fovy - 0.8f32.to_degrees() / 2.0).abs() < 1e-4);
+    }
+}
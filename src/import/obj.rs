@@ -0,0 +1,372 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use cgmath::Matrix4;
+
+use crate::data_types::{Geometry, Material, MeshVertex};
+use crate::mesh::Mesh;
+use crate::scene::Scene;
+use crate::texture::Texture;
+
+/// Loads a Wavefront OBJ file and its MTL materials into `scene`, placing every object with
+/// `transform`. Returns the indices of the geometry that was added.
+///
+/// Each object, group and material change becomes its own mesh. Polygons are fan
+/// triangulated. Meshes without normals keep zeroed ones so the hit shader uses the face
+/// normal and flat surfaces stay flat.
+pub fn load(scene: &mut Scene, path: &Path, transform: Matrix4<f32>) -> Result<Vec<usize>> {
+    let options = tobj::LoadOptions {
+        single_index: true,
+        triangulate: true,
+        ignore_points: true,
+        ignore_lines: true,
+    };
+    let (models, obj_materials) = tobj::load_obj(path, &options)
+        .with_context(|| format!("Unable to load {}", path.display()))?;
+
+    // A missing or broken MTL file shouldn't stop the geometry from loading
+    let obj_materials = obj_materials.unwrap_or_else(|e| {
+        log::warn!("Ignoring materials for {}: {}", path.display(), e);
+        Vec::new()
+    });
+
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut textures = HashMap::<PathBuf, Option<u32>>::new();
+    let mut material_ids = Vec::with_capacity(obj_materials.len());
+    for obj_material in &obj_materials {
+        let material = convert_material(scene, obj_material, base_dir, &mut textures);
+        material_ids.push(scene.add_material(material));
+    }
+    let mut default_material = None;
+
+    let mut added = Vec::with_capacity(models.len());
+    for model in models {
+        let mesh = convert_mesh(&model.mesh);
+        if mesh.indices.is_empty() {
+            continue;
+        }
+
+        let material_id = match model.mesh.material_id {
+            Some(id) if id < material_ids.len() => material_ids[id],
+            _ => *default_material.get_or_insert_with(|| scene.add_material(Material::default())),
+        };
+        let mesh_id = scene.add_mesh(mesh);
+        added.push(scene.add_geometry(Geometry::mesh(mesh_id, transform, material_id)));
+    }
+
+    Ok(added)
+}
+
+fn convert_mesh(obj_mesh: &tobj::Mesh) -> Mesh {
+    let vertex_count = obj_mesh.positions.len() / 3;
+    let has_normals = obj_mesh.normals.len() == obj_mesh.positions.len();
+    let has_uvs = obj_mesh.texcoords.len() / 2 == vertex_count;
+
+    let vertices = (0..vertex_count)
+        .map(|i| {
+            let position = [
+                obj_mesh.positions[3 * i],
+                obj_mesh.positions[3 * i + 1],
+                obj_mesh.positions[3 * i + 2],
+            ];
+            let normal = if has_normals {
+                [
+                    obj_mesh.normals[3 * i],
+                    obj_mesh.normals[3 * i + 1],
+                    obj_mesh.normals[3 * i + 2],
+                ]
+            } else {
+                [0.0; 3]
+            };
+            // OBJ puts v = 0 at the bottom of the image, textures are stored top down
+            let uv = if has_uvs {
+                [
+                    obj_mesh.texcoords[2 * i],
+                    1.0 - obj_mesh.texcoords[2 * i + 1],
+                ]
+            } else {
+                [0.0; 2]
+            };
+            MeshVertex::new(position, normal, uv)
+        })
+        .collect();
+
+    Mesh::new(vertices, obj_mesh.indices.clone())
+}
+
+/// Maps the Phong style MTL parameters onto the closest material we support:
+/// - Ke (not part of the original spec, but exported by Blender) makes it emissive
+/// - illum 4, 6 and 7 or a dissolve below 1 make it glass with Ni as the index of refraction
+/// - illum 3 and 5 make it metal tinted by Ks, with the roughness derived from Ns
+/// - everything else is diffuse with Kd and map_Kd, or just Kd if the texture can't be loaded
+fn convert_material(
+    scene: &mut Scene,
+    obj_material: &tobj::Material,
+    base_dir: &Path,
+    textures: &mut HashMap<PathBuf, Option<u32>>,
+) -> Material {
+    if let Some(emission) = obj_material
+        .unknown_param
+        .get("Ke")
+        .and_then(|ke| parse_color(ke))
+    {
+        if emission.iter().any(|&c| c > 0.0) {
+            return Material::emissive(emission);
+        }
+    }
+
+    let illum = obj_material.illumination_model.unwrap_or(2);
+    if matches!(illum, 4 | 6 | 7) || obj_material.dissolve < 1.0 {
+        let ior = if obj_material.optical_density > 1.0 {
+            obj_material.optical_density
+        } else {
+            1.5
+        };
+        return Material::dielectric(ior);
+    }
+
+    if matches!(illum, 3 | 5) {
+        // Blinn-Phong exponent to an approximate roughness
+        let roughness = (2.0 / (obj_material.shininess + 2.0)).sqrt();
+        return Material::metal(obj_material.specular, roughness);
+    }
+
+    let mut material = Material::lambertian(obj_material.diffuse);
+    if !obj_material.diffuse_texture.is_empty() {
+        let texture_path = base_dir.join(&obj_material.diffuse_texture);
+        let texture_id = match textures.get(&texture_path) {
+            Some(&id) => id,
+            // Like a broken MTL file, a texture that won't load only loses the detail. The
+            // failure is cached as well so materials sharing it don't retry and warn again.
+            None => {
+                let id = match Texture::load(&texture_path) {
+                    Ok(texture) => Some(scene.add_texture(texture)),
+                    Err(e) => {
+                        log::warn!(
+                            "Using the plain Kd color for {}: {:#}",
+                            obj_material.name,
+                            e
+                        );
+                        None
+                    }
+                };
+                textures.insert(texture_path, id);
+                id
+            }
+        };
+        if let Some(id) = texture_id {
+            material.texture_id = id as i32;
+        }
+    }
+    material
+}
+
+fn parse_color(value: &str) -> Option<[f32; 3]> {
+    let channels = value
+        .split_whitespace()
+        .map(str::parse::<f32>)
+        .collect::<Result<Vec<_>, _>>()
+        .ok()?;
+    match channels.as_slice() {
+        [r, g, b] => Some([*r, *g, *b]),
+        [v] => Some([*v; 3]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::MaterialType;
+    use cgmath::SquareMatrix;
+    use std::fs;
+
+    /// Writes `files` into a fresh directory under the system temp dir.
+    fn fixture(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("oscuras-obj-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (file, contents) in files {
+            fs::write(dir.join(file), contents).unwrap();
+        }
+        dir
+    }
+
+    fn load_fixture(dir: &Path) -> (Scene, Vec<usize>) {
+        let mut scene = Scene::empty();
+        let added = load(&mut scene, &dir.join("model.obj"), Matrix4::identity()).unwrap();
+        (scene, added)
+    }
+
+    fn material_of(scene: &Scene, geometry: usize) -> Material {
+        scene.materials[scene.geometry[geometry].material_id as usize]
+    }
+
+    #[test]
+    fn polygons_are_triangulated() {
+        let dir = fixture(
+            "polygons",
+            &[(
+                "model.obj",
+                "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv -1 0.5 0\n\
+                 f 1 2 3 4\nf 1 2 3 4 5\n",
+            )],
+        );
+        let (scene, added) = load_fixture(&dir);
+
+        assert_eq!(added.len(), 1);
+        let mesh = &scene.meshes[0];
+        assert_eq!(mesh.indices.len(), 3 * (2 + 3));
+        for triangle in mesh.indices.chunks(3) {
+            assert!(triangle.iter().all(|&i| (i as usize) < mesh.vertices.len()));
+            assert!(triangle[0] != triangle[1] && triangle[1] != triangle[2]);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn objects_and_groups_become_meshes() {
+        let dir = fixture(
+            "objects",
+            &[(
+                "model.obj",
+                "v 0 0 0\nv 1 0 0\nv 0 1 0\n\
+                 o first\nf 1 2 3\n\
+                 o second\nf 3 2 1\n\
+                 g third\nf 1 3 2\n",
+            )],
+        );
+        let mut scene = Scene::empty();
+        let transform = Matrix4::from_translation(cgmath::vec3(0.0, 2.0, 0.0));
+        let added = load(&mut scene, &dir.join("model.obj"), transform).unwrap();
+
+        assert_eq!(added, vec![0, 1, 2]);
+        assert_eq!(scene.meshes.len(), 3);
+        let mesh_ids: Vec<_> = scene.geometry.iter().map(|g| g.mesh_id).collect();
+        assert_eq!(mesh_ids, vec![0, 1, 2]);
+        let transf: [[f32; 4]; 4] = transform.into();
+        assert!(scene.geometry.iter().all(|g| g.transf == transf));
+
+        // Without an MTL file they all share one default material
+        assert_eq!(scene.materials.len(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn faces_without_normals() {
+        let dir = fixture(
+            "normals",
+            &[(
+                "model.obj",
+                "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nvt 0.25 0.75\n\
+                 o flat\nf 1 2 3\n\
+                 o smooth\nf 1/1/1 2/1/1 3/1/1\n",
+            )],
+        );
+        let (scene, _) = load_fixture(&dir);
+
+        let flat = &scene.meshes[0];
+        assert!(flat.vertices.iter().all(|v| v.normal == [0.0; 3]));
+        let smooth = &scene.meshes[1];
+        assert!(smooth.vertices.iter().all(|v| v.normal == [0.0, 0.0, 1.0]));
+        assert!(smooth.vertices.iter().all(|v| v.uv == [0.25, 0.25]));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn mtl_materials() {
+        let dir = fixture(
+            "materials",
+            &[
+                (
+                    "model.obj",
+                    "mtllib model.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\n\
+                     usemtl light\nf 1 2 3\n\
+                     usemtl water\nf 1 2 3\n\
+                     usemtl smoke\nf 1 2 3\n\
+                     usemtl gold\nf 1 2 3\n\
+                     usemtl clay\nf 1 2 3\n",
+                ),
+                (
+                    "model.mtl",
+                    "newmtl light\nKd 0.5 0.5 0.5\nKe 4 3 2\n\
+                     newmtl water\nillum 7\nNi 1.33\n\
+                     newmtl smoke\nd 0.5\n\
+                     newmtl gold\nillum 3\nKs 0.9 0.8 0.7\nNs 98\n\
+                     newmtl clay\nillum 2\nKd 0.1 0.2 0.3\nKs 1 1 1\n",
+                ),
+            ],
+        );
+        let (scene, added) = load_fixture(&dir);
+        assert_eq!(added.len(), 5);
+
+        let light = material_of(&scene, added[0]);
+        assert_eq!(light.ty, MaterialType::EMISSIVE);
+        assert_eq!(light.emission, [4.0, 3.0, 2.0]);
+
+        let water = material_of(&scene, added[1]);
+        assert_eq!(water.ty, MaterialType::DIELECTRIC);
+        assert_eq!(water.ior, 1.33);
+
+        // Dissolve alone makes it glass, without Ni it gets a typical glass index
+        let smoke = material_of(&scene, added[2]);
+        assert_eq!(smoke.ty, MaterialType::DIELECTRIC);
+        assert_eq!(smoke.ior, 1.5);
+
+        let gold = material_of(&scene, added[3]);
+        assert_eq!(gold.ty, MaterialType::METAL);
+        assert_eq!(gold.albedo, [0.9, 0.8, 0.7]);
+        assert!((gold.roughness - 0.02f32.sqrt()).abs() < 1e-6);
+
+        let clay = material_of(&scene, added[4]);
+        assert_eq!(clay.ty, MaterialType::LAMBERTIAN);
+        assert_eq!(clay.albedo, [0.1, 0.2, 0.3]);
+        assert_eq!(clay.texture_id, -1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn diffuse_textures() {
+        let dir = fixture(
+            "textures",
+            &[
+                (
+                    "model.obj",
+                    "mtllib model.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\n\
+                     usemtl brick\nf 1 2 3\n\
+                     usemtl tile\nf 1 2 3\n\
+                     usemtl missing\nf 1 2 3\n\
+                     usemtl also_missing\nf 1 2 3\n",
+                ),
+                (
+                    "model.mtl",
+                    "newmtl brick\nKd 1 1 1\nmap_Kd red.png\n\
+                     newmtl tile\nKd 0.5 0.5 0.5\nmap_Kd red.png\n\
+                     newmtl missing\nKd 0.1 0.2 0.3\nmap_Kd missing.png\n\
+                     newmtl also_missing\nKd 0.3 0.2 0.1\nmap_Kd missing.png\n",
+                ),
+            ],
+        );
+        image::RgbaImage::from_pixel(2, 2, image::Rgba([255, 0, 0, 255]))
+            .save(dir.join("red.png"))
+            .unwrap();
+        let (scene, added) = load_fixture(&dir);
+
+        // Loaded once and shared
+        assert_eq!(scene.textures.len(), 1);
+        assert_eq!(scene.textures[0].texels, vec![0xff00_00ff; 4]);
+        assert_eq!(material_of(&scene, added[0]).texture_id, 0);
+        assert_eq!(material_of(&scene, added[1]).texture_id, 0);
+
+        // A texture that can't be loaded leaves the plain Kd color
+        let missing = material_of(&scene, added[2]);
+        assert_eq!(missing.ty, MaterialType::LAMBERTIAN);
+        assert_eq!(missing.albedo, [0.1, 0.2, 0.3]);
+        assert_eq!(missing.texture_id, -1);
+        let also_missing = material_of(&scene, added[3]);
+        assert_eq!(also_missing.albedo, [0.3, 0.2, 0.1]);
+        assert_eq!(also_missing.texture_id, -1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,729 @@
+//! CPU versions of the ray tests in `calculate_intersections.comp`. They follow the shader
+//! line for line so hits can be checked without a GPU.
+
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Vector2, Vector3, Vector4};
+
+use crate::bvh::{Bvh, SceneBvh};
+use crate::data_types::{BvhNode, GeomType, Geometry};
+use crate::mesh::Mesh;
+use crate::scene::Scene;
+
+const EPSILON: f32 = 1e-4;
+
+/// Closest hit along a ray. `t` is measured along the world space direction as given, and
+/// `normal` is the world space normal pointing out of the surface.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hit {
+    pub t: f32,
+    pub normal: Vector3<f32>,
+}
+
+/// Dispatches on the geometry type, like the loop in the hit shader. Meshes need their
+/// triangles, so they go through `mesh_intersect` instead.
+pub fn intersect(geom: &Geometry, origin: Point3<f32>, direction: Vector3<f32>) -> Option<Hit> {
+    if geom.ty == GeomType::SPHERE {
+        sphere_intersect(geom, origin, direction)
+    } else if geom.ty == GeomType::BOX {
+        box_intersect(geom, origin, direction)
+    } else {
+        None
+    }
+}
+
+/// Closest hit against everything in the scene, walking both levels of `bvh` like the hit
+/// shader. Hits at a non-finite `t`, like from a degenerate transform, are ignored.
+pub fn closest_hit(
+    scene: &Scene,
+    bvh: &SceneBvh,
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+) -> Option<Hit> {
+    let hit_geometry = |geom: &Geometry| {
+        if geom.ty == GeomType::TRIANGLE {
+            let mesh_id = geom.mesh_id as usize;
+            let (mesh, mesh_bvh) = (&scene.meshes[mesh_id], &bvh.bottom_level[mesh_id]);
+            mesh_intersect(geom, mesh, mesh_bvh, origin, direction)
+        } else {
+            intersect(geom, origin, direction)
+        }
+    };
+
+    let mut closest = None;
+    let top_level = &bvh.top_level;
+    walk(top_level, origin.to_vec(), direction, |index, t_max| {
+        let hit = hit_geometry(&scene.geometry[index as usize]);
+        match hit {
+            Some(hit) if hit.t.is_finite() && hit.t < t_max => {
+                closest = Some(hit);
+                Some(hit.t)
+            }
+            _ => None,
+        }
+    });
+    closest
+}
+
+/// Unit sphere at the origin of object space.
+pub fn sphere_intersect(
+    sphere: &Geometry,
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+) -> Option<Hit> {
+    let radius = 1.0;
+    let (r_orig, r_dir) = to_object_space(sphere, origin, direction);
+
+    let a = r_dir.dot(r_dir);
+    let half_b = r_orig.dot(r_dir);
+    let c = r_orig.dot(r_orig) - radius * radius;
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    // Take the far root when the ray starts inside the sphere
+    let root = discriminant.sqrt();
+    let mut t = (-half_b - root) / a;
+    if t < EPSILON {
+        t = (-half_b + root) / a;
+        if t < EPSILON {
+            return None;
+        }
+    }
+
+    let object_point = r_orig + t * r_dir;
+    Some(Hit {
+        t,
+        normal: to_world_normal(sphere, object_point),
+    })
+}
+
+/// Unit cube spanning [-0.5, 0.5] on every axis of object space.
+pub fn box_intersect(cube: &Geometry, origin: Point3<f32>, direction: Vector3<f32>) -> Option<Hit> {
+    let (r_orig, r_dir) = to_object_space(cube, origin, direction);
+
+    let mut t_near = f32::NEG_INFINITY;
+    let mut t_far = f32::INFINITY;
+    let mut near_normal = Vector3::new(0.0, 0.0, 0.0);
+    let mut far_normal = Vector3::new(0.0, 0.0, 0.0);
+
+    for axis in 0..3 {
+        let o = r_orig[axis];
+        let d = r_dir[axis];
+        if d.abs() < 1e-8 {
+            // Parallel to this slab, either always inside it or never
+            if !(-0.5..=0.5).contains(&o) {
+                return None;
+            }
+            continue;
+        }
+
+        let mut t1 = (-0.5 - o) / d;
+        let mut t2 = (0.5 - o) / d;
+        let mut face_normal = Vector3::new(0.0, 0.0, 0.0);
+        face_normal[axis] = -1.0;
+        if t1 > t2 {
+            std::mem::swap(&mut t1, &mut t2);
+            face_normal = -face_normal;
+        }
+
+        if t1 > t_near {
+            t_near = t1;
+            near_normal = face_normal;
+        }
+        if t2 < t_far {
+            t_far = t2;
+            far_normal = -face_normal;
+        }
+    }
+
+    if t_near > t_far {
+        return None;
+    }
+
+    let (t, object_normal) = if t_near > EPSILON {
+        (t_near, near_normal)
+    } else if t_far > EPSILON {
+        (t_far, far_normal)
+    } else {
+        return None;
+    };
+
+    Some(Hit {
+        t,
+        normal: to_world_normal(cube, object_normal),
+    })
+}
+
+// The direction isn't renormalized so t stays the same along the world space ray
+fn to_object_space(
+    geom: &Geometry,
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+) -> (Vector3<f32>, Vector3<f32>) {
+    let inverse = Matrix4::from(geom.inverse);
+    let r_orig = inverse * origin.to_homogeneous();
+    let r_dir = inverse * direction.extend(0.0);
+    (r_orig.truncate(), r_dir.truncate())
+}
+
+fn to_world_normal(geom: &Geometry, object_normal: Vector3<f32>) -> Vector3<f32> {
+    let transp_inv = Matrix4::from(geom.transp_inv);
+    let normal: Vector4<f32> = transp_inv * object_normal.extend(0.0);
+    normal.truncate().normalize()
+}
+
+/// Placed instance of `mesh`, walking its bottom level `bvh` in object space.
+pub fn mesh_intersect(
+    geom: &Geometry,
+    mesh: &Mesh,
+    bvh: &Bvh,
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+) -> Option<Hit> {
+    let (r_orig, r_dir) = to_object_space(geom, origin, direction);
+
+    let mut best: Option<(f32, usize, Vector3<f32>)> = None;
+    walk(bvh, r_orig, r_dir, |first, t_max| {
+        let first = first as usize;
+        let [v0, v1, v2] = triangle_positions(mesh, first);
+        match triangle_intersect(v0, v1, v2, r_orig, r_dir) {
+            Some((t, bary)) if t > EPSILON && t < t_max => {
+                best = Some((t, first, bary));
+                Some(t)
+            }
+            _ => None,
+        }
+    });
+    let (t, first, bary) = best?;
+
+    let (object_normal, _) = interpolate(mesh, first, bary);
+    Some(Hit {
+        t,
+        normal: to_world_normal(geom, object_normal),
+    })
+}
+
+/// Object space normal and texture coordinates at barycentric weights `bary` of the triangle
+/// starting at index `first`.
+fn interpolate(mesh: &Mesh, first: usize, bary: Vector3<f32>) -> (Vector3<f32>, Vector2<f32>) {
+    let vertex = |corner: usize| mesh.vertices[mesh.indices[first + corner] as usize];
+    let (a, b, c) = (vertex(0), vertex(1), vertex(2));
+
+    // Meshes without normals store zeros, fall back to the face normal
+    let mut normal = Vector3::from(a.normal) * bary.x
+        + Vector3::from(b.normal) * bary.y
+        + Vector3::from(c.normal) * bary.z;
+    if normal.magnitude2() < 1e-12 {
+        let [v0, v1, v2] = triangle_positions(mesh, first);
+        normal = (v1 - v0).cross(v2 - v0);
+    }
+    let uv =
+        Vector2::from(a.uv) * bary.x + Vector2::from(b.uv) * bary.y + Vector2::from(c.uv) * bary.z;
+    (normal, uv)
+}
+
+/// Visits the primitives in every leaf the ray enters before the closest hit so far.
+/// `test` gets each primitive and that distance, and returns the distance of a closer hit.
+fn walk(
+    bvh: &Bvh,
+    r_orig: Vector3<f32>,
+    r_dir: Vector3<f32>,
+    mut test: impl FnMut(u32, f32) -> Option<f32>,
+) {
+    if bvh.bounds().is_empty() {
+        return;
+    }
+
+    let inv_dir = Vector3::new(1.0 / r_dir.x, 1.0 / r_dir.y, 1.0 / r_dir.z);
+    let mut t_max = f32::INFINITY;
+    let mut stack = vec![0];
+    while let Some(index) = stack.pop() {
+        let node = &bvh.nodes[index];
+        if !node_hit(node, r_orig, inv_dir, t_max) {
+            continue;
+        }
+        if node.count > 0 {
+            let first = node.offset as usize;
+            for &primitive in &bvh.primitives[first..first + node.count as usize] {
+                if let Some(t) = test(primitive, t_max) {
+                    t_max = t;
+                }
+            }
+        } else {
+            stack.push(node.offset as usize);
+            stack.push(index + 1);
+        }
+    }
+}
+
+// Slab test against a node's bounds, `aabb_intersect_test` in the shader
+fn node_hit(node: &BvhNode, r_orig: Vector3<f32>, inv_dir: Vector3<f32>, t_max: f32) -> bool {
+    let mut t_near = 0.0f32;
+    let mut t_far = t_max;
+    for axis in 0..3 {
+        let (min, max, o) = (node.min[axis], node.max[axis], r_orig[axis]);
+        if inv_dir[axis].is_infinite() {
+            // Parallel to this slab, an origin right on its edge would make the distances NaN
+            if o < min || o > max {
+                return false;
+            }
+            continue;
+        }
+        let t1 = (min - o) * inv_dir[axis];
+        let t2 = (max - o) * inv_dir[axis];
+        t_near = t_near.max(t1.min(t2));
+        t_far = t_far.min(t1.max(t2));
+    }
+    t_near <= t_far
+}
+
+fn triangle_positions(mesh: &Mesh, first: usize) -> [Vector3<f32>; 3] {
+    let position = |corner: usize| {
+        Vector3::from(mesh.vertices[mesh.indices[first + corner] as usize].position)
+    };
+    [position(0), position(1), position(2)]
+}
+
+/// Watertight ray/triangle test, returning `t` and the barycentric weights of the three
+/// corners.
+fn triangle_intersect(
+    v0: Vector3<f32>,
+    v1: Vector3<f32>,
+    v2: Vector3<f32>,
+    r_orig: Vector3<f32>,
+    r_dir: Vector3<f32>,
+) -> Option<(f32, Vector3<f32>)> {
+    // Axis where the direction is largest becomes z, winding is kept by swapping x and y
+    let abs_dir = Vector3::new(r_dir.x.abs(), r_dir.y.abs(), r_dir.z.abs());
+    let kz = if abs_dir.x > abs_dir.y {
+        if abs_dir.x > abs_dir.z {
+            0
+        } else {
+            2
+        }
+    } else if abs_dir.y > abs_dir.z {
+        1
+    } else {
+        2
+    };
+    let mut kx = (kz + 1) % 3;
+    let mut ky = (kx + 1) % 3;
+    if r_dir[kz] < 0.0 {
+        std::mem::swap(&mut kx, &mut ky);
+    }
+
+    let sx = r_dir[kx] / r_dir[kz];
+    let sy = r_dir[ky] / r_dir[kz];
+    let sz = 1.0 / r_dir[kz];
+
+    let a = v0 - r_orig;
+    let b = v1 - r_orig;
+    let c = v2 - r_orig;
+
+    let ax = a[kx] - sx * a[kz];
+    let ay = a[ky] - sy * a[kz];
+    let bx = b[kx] - sx * b[kz];
+    let by = b[ky] - sy * b[kz];
+    let cx = c[kx] - sx * c[kz];
+    let cy = c[ky] - sy * c[kz];
+
+    let u = cx * by - cy * bx;
+    let v = ax * cy - ay * cx;
+    let w = bx * ay - by * ax;
+
+    if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+        return None;
+    }
+
+    let det = u + v + w;
+    if det == 0.0 {
+        return None;
+    }
+
+    let az = sz * a[kz];
+    let bz = sz * b[kz];
+    let cz = sz * c[kz];
+    let t = (u * az + v * bz + w * cz) / det;
+
+    Some((t, Vector3::new(u, v, w) / det))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh::{BvhBuildOptions, SplitMethod};
+    use crate::data_types::MeshVertex;
+    use cgmath::{Deg, Matrix, SquareMatrix};
+
+    fn assert_close(a: Vector3<f32>, b: Vector3<f32>) {
+        assert!((a - b).magnitude() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn box_axis_aligned() {
+        let cube = Geometry::new(
+            GeomType::BOX,
+            Matrix4::from_translation(Vector3::new(0.0, 0.0, 5.0)),
+            0,
+        );
+        let origin = Point3::new(0.2, -0.3, 0.0);
+
+        let hit = box_intersect(&cube, origin, Vector3::new(0.0, 0.0, 1.0)).unwrap();
+        assert!((hit.t - 4.5).abs() < 1e-5);
+        assert_close(hit.normal, Vector3::new(0.0, 0.0, -1.0));
+
+        // t is along the direction as given, not a normalized one
+        let hit = box_intersect(&cube, origin, Vector3::new(0.0, 0.0, 2.0)).unwrap();
+        assert!((hit.t - 2.25).abs() < 1e-5);
+    }
+
+    #[test]
+    fn box_rotated_and_scaled() {
+        // Scaling after the rotation shears the cube, so its normals aren't just rotated
+        let transform = Matrix4::from_translation(Vector3::new(0.0, 0.0, 5.0))
+            * Matrix4::from_nonuniform_scale(2.0, 1.0, 0.5)
+            * Matrix4::from_angle_y(Deg(30.0));
+        let cube = Geometry::new(GeomType::BOX, transform, 0);
+        let origin = Point3::new(0.1, 0.2, 0.0);
+        let direction = Vector3::new(0.0, 0.0, 1.0);
+        let hit = box_intersect(&cube, origin, direction).unwrap();
+
+        // The hit lies on a face of the object space cube
+        let inverse = transform.invert().unwrap();
+        let world_point = origin + hit.t * direction;
+        let object_point = (inverse * world_point.to_homogeneous()).truncate();
+        let axis = (0..3)
+            .max_by(|&a, &b| {
+                object_point[a]
+                    .abs()
+                    .partial_cmp(&object_point[b].abs())
+                    .unwrap()
+            })
+            .unwrap();
+        assert!((object_point[axis].abs() - 0.5).abs() < 1e-4);
+
+        let mut object_normal = Vector3::new(0.0, 0.0, 0.0);
+        object_normal[axis] = object_point[axis].signum();
+        let expected = (inverse.transpose() * object_normal.extend(0.0))
+            .truncate()
+            .normalize();
+        assert_close(hit.normal, expected);
+        assert!(hit.normal.dot(direction) < 0.0);
+
+        // And stays perpendicular to that face in world space
+        for edge_axis in (0..3).filter(|&a| a != axis) {
+            let mut edge = Vector3::new(0.0, 0.0, 0.0);
+            edge[edge_axis] = 1.0;
+            let world_edge = (transform * edge.extend(0.0)).truncate();
+            assert!(hit.normal.dot(world_edge).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn box_from_inside() {
+        let cube = Geometry::new(GeomType::BOX, Matrix4::from_scale(2.0), 0);
+        let hit = box_intersect(
+            &cube,
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+        )
+        .unwrap();
+        assert!((hit.t - 1.0).abs() < 1e-5);
+        assert_close(hit.normal, Vector3::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn box_parallel_to_slab() {
+        let cube = Geometry::new(GeomType::BOX, Matrix4::from_scale(1.0), 0);
+        let direction = Vector3::new(0.0, 0.0, 1.0);
+
+        let inside = box_intersect(&cube, Point3::new(0.25, 0.0, -5.0), direction).unwrap();
+        assert!((inside.t - 4.5).abs() < 1e-5);
+        assert_close(inside.normal, Vector3::new(0.0, 0.0, -1.0));
+
+        assert_eq!(
+            box_intersect(&cube, Point3::new(0.75, 0.0, -5.0), direction),
+            None
+        );
+    }
+
+    #[test]
+    fn box_miss() {
+        let cube = Geometry::new(GeomType::BOX, Matrix4::from_scale(1.0), 0);
+        let origin = Point3::new(0.0, 0.0, -5.0);
+
+        // Passing beside it, and pointing away from it
+        assert_eq!(
+            box_intersect(&cube, origin, Vector3::new(0.0, 0.2, 1.0)),
+            None
+        );
+        assert_eq!(
+            box_intersect(&cube, origin, Vector3::new(0.0, 0.0, -1.0)),
+            None
+        );
+    }
+
+    fn triangle_mesh(positions: &[[f32; 3]], indices: Vec<u32>) -> Mesh {
+        let vertices = positions
+            .iter()
+            .map(|&position| MeshVertex::new(position, [0.0; 3], [0.0; 2]))
+            .collect();
+        Mesh::new(vertices, indices)
+    }
+
+    #[test]
+    fn triangle_shared_edge_is_watertight() {
+        // A quad split along its diagonal, hit all along that edge by an oblique ray
+        let quad = triangle_mesh(
+            &[
+                [0.0, 0.0, 0.0],
+                [1.3, 0.0, 0.1],
+                [1.3, 0.7, 0.3],
+                [0.0, 0.7, 0.2],
+            ],
+            vec![0, 1, 2, 0, 2, 3],
+        );
+        let [v0, v1, v2] = triangle_positions(&quad, 0);
+        let [w0, w1, w2] = triangle_positions(&quad, 3);
+        let direction = Vector3::new(0.3, -0.2, 1.0);
+
+        // Including both ends, where the edge meets the vertices of the other corners
+        for step in 0..=200 {
+            let on_edge = v0 + (v2 - v0) * (step as f32 / 200.0);
+            let origin = on_edge - direction * 3.0;
+            let first = triangle_intersect(v0, v1, v2, origin, direction);
+            let second = triangle_intersect(w0, w1, w2, origin, direction);
+            assert!(
+                first.is_some() || second.is_some(),
+                "Ray through {:?} slipped between the triangles",
+                on_edge
+            );
+        }
+    }
+
+    #[test]
+    fn triangle_backface() {
+        let triangle = triangle_mesh(
+            &[[-1.0, -1.0, 0.0], [1.0, -1.0, 0.0], [0.0, 1.0, 0.0]],
+            vec![0, 1, 2],
+        );
+        let geom = Geometry::mesh(0, Matrix4::from_translation(Vector3::new(0.0, 0.0, 2.0)), 0);
+        let bvh = Bvh::over_mesh(&triangle, &BvhBuildOptions::default());
+
+        let front = mesh_intersect(
+            &geom,
+            &triangle,
+            &bvh,
+            Point3::new(0.0, 0.0, 5.0),
+            Vector3::new(0.0, 0.0, -1.0),
+        )
+        .unwrap();
+        let back = mesh_intersect(
+            &geom,
+            &triangle,
+            &bvh,
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+        )
+        .unwrap();
+        assert!((front.t - 3.0).abs() < 1e-5);
+        assert!((back.t - 2.0).abs() < 1e-5);
+
+        // Without vertex normals both sides get the same face normal, from the winding
+        assert_close(front.normal, Vector3::new(0.0, 0.0, 1.0));
+        assert_close(back.normal, front.normal);
+    }
+
+    #[test]
+    fn triangle_parallel_ray() {
+        let v0 = Vector3::new(0.0, 0.0, 0.0);
+        let v1 = Vector3::new(1.0, 0.0, 0.0);
+        let v2 = Vector3::new(0.0, 1.0, 0.0);
+        let direction = Vector3::new(1.0, 0.0, 0.0);
+
+        // In the triangle's plane, and just above it
+        for &z in &[0.0, 0.5] {
+            let origin = Vector3::new(-1.0, 0.25, z);
+            assert_eq!(triangle_intersect(v0, v1, v2, origin, direction), None);
+        }
+    }
+
+    #[test]
+    fn triangle_miss() {
+        let v0 = Vector3::new(0.0, 0.0, 0.0);
+        let v1 = Vector3::new(1.0, 0.0, 0.0);
+        let v2 = Vector3::new(0.0, 1.0, 0.0);
+        let origin = Vector3::new(0.6, 0.6, -1.0);
+        assert_eq!(
+            triangle_intersect(v0, v1, v2, origin, Vector3::new(0.0, 0.0, 1.0)),
+            None
+        );
+    }
+
+    // The default spheres with a wall of triangles behind them
+    fn scene_with_mesh() -> Scene {
+        let mut scene = Scene::new();
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for y in 0..5 {
+            for x in 0..5 {
+                let position = [x as f32 - 2.0, y as f32 - 2.0, 0.0];
+                vertices.push(MeshVertex::new(position, [0.0; 3], [0.0; 2]));
+            }
+        }
+        for y in 0..4 {
+            for x in 0..4 {
+                let corner = y * 5 + x;
+                indices.extend(&[
+                    corner,
+                    corner + 1,
+                    corner + 6,
+                    corner,
+                    corner + 6,
+                    corner + 5,
+                ]);
+            }
+        }
+        let mesh_id = scene.add_mesh(Mesh::new(vertices, indices));
+        let transform =
+            Matrix4::from_translation(Vector3::new(0.0, 0.0, 8.0)) * Matrix4::from_scale(2.0);
+        scene.add_geometry(Geometry::mesh(mesh_id, transform, 0));
+        scene
+    }
+
+    #[test]
+    fn closest_hit_matches_testing_everything() {
+        let scene = scene_with_mesh();
+        let bvh = SceneBvh::build(&scene, &BvhBuildOptions::default());
+        // A single leaf per level, so every primitive is tested
+        let everything = BvhBuildOptions {
+            split: SplitMethod::Median,
+            max_leaf_size: usize::MAX,
+            ..BvhBuildOptions::default()
+        };
+        let single_leaf = SceneBvh::build(&scene, &everything);
+
+        let origin = Point3::new(0.0, 0.5, -1.0);
+        let mut hits = 0;
+        for y in -10..=10 {
+            for x in -10..=10 {
+                let direction = Vector3::new(x as f32 * 0.07, y as f32 * 0.07, 1.0);
+                let hit = closest_hit(&scene, &bvh, origin, direction);
+                let expected = closest_hit(&scene, &single_leaf, origin, direction);
+                match (hit, expected) {
+                    (Some(hit), Some(expected)) => {
+                        assert!((hit.t - expected.t).abs() < 1e-5);
+                        assert_close(hit.normal, expected.normal);
+                        hits += 1;
+                    }
+                    (hit, expected) => assert_eq!(hit, expected),
+                }
+            }
+        }
+        assert!(hits > 0);
+    }
+
+    #[test]
+    fn closest_hit_on_mesh() {
+        let scene = scene_with_mesh();
+        let bvh = SceneBvh::build(&scene, &BvhBuildOptions::default());
+
+        // Between the spheres and above the ground, straight into the wall
+        let origin = Point3::new(1.0, 1.5, 0.0);
+        let hit = closest_hit(&scene, &bvh, origin, Vector3::new(0.0, 0.0, 1.0)).unwrap();
+        assert!((hit.t - 8.0).abs() < 1e-5);
+        assert_close(hit.normal.map(f32::abs), Vector3::new(0.0, 0.0, 1.0));
+
+        // Off its edge
+        let origin = Point3::new(4.5, 1.5, 0.0);
+        assert_eq!(
+            closest_hit(&scene, &bvh, origin, Vector3::new(0.0, 0.0, 1.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn triangle_interpolates_attributes() {
+        let corners = [[0.0, 0.0, 0.0], [2.0, 0.0, 0.0], [0.0, 2.0, 0.0]];
+        let normals = [[1.0, 0.0, 1.0], [0.0, 1.0, 1.0], [0.0, 0.0, 1.0]];
+        let uvs = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]];
+        let vertices = (0..3)
+            .map(|i| MeshVertex::new(corners[i], normals[i], uvs[i]))
+            .collect();
+        let mesh = Mesh::new(vertices, vec![0, 1, 2]);
+
+        // Aim at weights 0.2, 0.3 and 0.5
+        let weights = Vector3::new(0.2, 0.3, 0.5);
+        let [v0, v1, v2] = triangle_positions(&mesh, 0);
+        let point = v0 * weights.x + v1 * weights.y + v2 * weights.z;
+        let origin = point + Vector3::new(0.0, 0.0, -2.0);
+        let (t, bary) =
+            triangle_intersect(v0, v1, v2, origin, Vector3::new(0.0, 0.0, 1.0)).unwrap();
+        assert!((t - 2.0).abs() < 1e-5);
+        assert_close(bary, weights);
+
+        let (normal, uv) = interpolate(&mesh, 0, bary);
+        assert_close(normal, Vector3::new(0.2, 0.3, 1.0));
+        assert!((uv - Vector2::new(0.3, 0.5)).magnitude() < 1e-5);
+
+        // The interpolated normal goes through the transpose inverse like any other
+        let transform = Matrix4::from_nonuniform_scale(1.0, 2.0, 1.0);
+        let geom = Geometry::mesh(0, transform, 0);
+        let world_origin = Point3::new(origin.x, origin.y * 2.0, origin.z);
+        let bvh = Bvh::over_mesh(&mesh, &BvhBuildOptions::default());
+        let direction = Vector3::new(0.0, 0.0, 1.0);
+        let hit = mesh_intersect(&geom, &mesh, &bvh, world_origin, direction).unwrap();
+        let expected = Vector3::new(0.2, 0.3 / 2.0, 1.0).normalize();
+        assert_close(hit.normal, expected);
+    }
+
+    #[test]
+    fn closest_hit_skips_non_finite() {
+        let mut scene = Scene::empty();
+        let sphere = |z| Matrix4::from_translation(Vector3::new(0.0, 0.0, z));
+        scene.add_geometry(Geometry::new(GeomType::SPHERE, sphere(5.0), 0));
+        // In front of the other one, but every hit on it comes out NaN
+        let mut broken = Geometry::new(GeomType::SPHERE, sphere(3.0), 0);
+        broken.inverse = [[f32::NAN; 4]; 4];
+        scene.add_geometry(broken);
+        let bvh = SceneBvh::build(&scene, &BvhBuildOptions::default());
+
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let hit = closest_hit(&scene, &bvh, origin, Vector3::new(0.0, 0.0, 1.0)).unwrap();
+        assert!((hit.t - 4.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn node_hit_clips_to_the_ray() {
+        let node = BvhNode {
+            min: [-1.0, -1.0, 2.0],
+            max: [1.0, 1.0, 4.0],
+            ..BvhNode::default()
+        };
+        // Straight down +z, parallel to the x and y slabs
+        let inv_dir = Vector3::new(f32::INFINITY, f32::INFINITY, 1.0);
+        let hits = |x, y, z, t_max| node_hit(&node, Vector3::new(x, y, z), inv_dir, t_max);
+
+        assert!(hits(0.0, 0.0, 0.0, f32::INFINITY));
+        // Starting beyond the closest hit so far, or entirely behind the origin
+        assert!(!hits(0.0, 0.0, 0.0, 1.5));
+        assert!(!hits(0.0, 0.0, 5.0, f32::INFINITY));
+        // From inside the box
+        assert!(hits(0.0, 0.0, 3.0, 0.5));
+
+        // Right on the edges of the parallel slabs, and just outside them
+        assert!(hits(1.0, -1.0, 0.0, f32::INFINITY));
+        assert!(!hits(1.001, 0.0, 0.0, f32::INFINITY));
+    }
+
+    #[test]
+    fn closest_hit_empty_scene() {
+        let scene = Scene::empty();
+        let bvh = SceneBvh::build(&scene, &BvhBuildOptions::default());
+        let direction = Vector3::new(0.0, 0.0, 1.0);
+        assert_eq!(
+            closest_hit(&scene, &bvh, Point3::new(0.0, 0.0, 0.0), direction),
+            None
+        );
+    }
+}
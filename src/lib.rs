@@ -0,0 +1,28 @@
+//! Oscuras is a pathtracer that runs on the GPU through wgpu.
+//!
+//! A render goes through three steps: build a [`Scene`] and a [`Camera`], create a
+//! [`Pathtracer`] on a `wgpu::Device` you own, then call [`Pathtracer::run`] on a command
+//! encoder for every iteration. The result lives in [`Pathtracer::texture`], or can be
+//! copied back to the CPU with [`Pathtracer::read_image`].
+
+pub mod bvh;
+pub mod camera;
+pub mod data_types;
+pub mod gpu_buffer;
+pub mod import;
+pub mod intersect;
+pub mod mesh;
+pub mod pathtracer;
+pub mod sampling;
+pub mod scene;
+pub mod scene_file;
+pub mod settings;
+pub mod texture;
+
+pub use camera::{Camera, Projection};
+pub use data_types::{GeomType, Geometry, Material, MaterialType, MeshVertex};
+pub use mesh::Mesh;
+pub use pathtracer::Pathtracer;
+pub use scene::Scene;
+pub use settings::{RenderSettings, Sampler};
+pub use texture::Texture;
@@ -1,21 +1,61 @@
+use structopt::StructOpt;
 use winit::{
     event::*,
     event_loop::{ControlFlow, EventLoop},
     window::WindowBuilder,
 };
 
+mod cli;
+mod headless;
 mod viewer;
 
+use cli::{Cli, Command, CommonOptions, RenderOptions, ViewOptions};
+use oscuras::scene_file::SceneFile;
+
 fn main() {
     env_logger::init();
+
+    let cli = Cli::from_args();
+    match cli
+        .command
+        .unwrap_or_else(|| Command::View(ViewOptions::default()))
+    {
+        Command::View(options) => run_viewer(options),
+        Command::Render(options) => render_headless(options),
+    }
+}
+
+fn load_scene_file(common: &CommonOptions) -> SceneFile {
+    common.scene_file().unwrap_or_else(|e| {
+        eprintln!("{:#}", e);
+        std::process::exit(1);
+    })
+}
+
+fn run_viewer(options: ViewOptions) {
+    let common = options.common;
+    let file = load_scene_file(&common);
+    let settings_file = file.settings;
+    let (width, height) = common.size(&settings_file);
+
     let event_loop = EventLoop::new();
-    let window = WindowBuilder::new().build(&event_loop).unwrap();
+    let window = WindowBuilder::new()
+        .with_inner_size(winit::dpi::PhysicalSize::new(width, height))
+        .build(&event_loop)
+        .unwrap();
     window.set_title("Oscuras");
 
     use futures::executor::block_on;
 
     // Since main can't be async, we're going to need to block
-    let mut view_window = block_on(viewer::Viewer::new(&window));
+    let mut view_window = block_on(viewer::Viewer::new(
+        &window,
+        common.backend,
+        file,
+        common.scene.as_deref(),
+        common.projection,
+        common.settings(&settings_file, Some(1)),
+    ));
 
     event_loop.run(move |event, _, control_flow| {
         match event {
@@ -65,3 +105,29 @@ fn main() {
         }
     });
 }
+
+fn render_headless(options: RenderOptions) {
+    let common = options.common;
+    let file = load_scene_file(&common);
+    let (width, height) = common.size(&file.settings);
+
+    use futures::executor::block_on;
+
+    let mut renderer = block_on(headless::Headless::new(
+        common.backend,
+        &file.scene,
+        &file
+            .camera
+            .with_projection(common.projection)
+            .camera(width, height),
+        common.settings(&file.settings, options.samples),
+    ));
+    if options.bvh_stats {
+        println!("BVH: {}", renderer.bvh_stats());
+    }
+    renderer.render();
+    if let Err(e) = renderer.save(&options.output) {
+        eprintln!("Failed to save {}: {}", options.output.display(), e);
+        std::process::exit(1);
+    }
+}
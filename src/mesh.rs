@@ -0,0 +1,23 @@
+use crate::data_types::MeshVertex;
+
+/// An indexed triangle mesh in object space. Every three indices make a triangle.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mesh {
+    pub vertices: Vec<MeshVertex>,
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    pub fn new(vertices: Vec<MeshVertex>, indices: Vec<u32>) -> Self {
+        debug_assert_eq!(
+            indices.len() % 3,
+            0,
+            "Mesh indices don't make whole triangles"
+        );
+        Self { vertices, indices }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+}
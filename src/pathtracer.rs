@@ -0,0 +1,983 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::Vector3;
+
+use crate::bvh::{Aabb, SceneBvh, SceneBvhStats, TopLevelUpdate};
+use crate::camera::Camera;
+use crate::data_types::*;
+use crate::gpu_buffer::{GPUBuffer, GPUBufferDescription};
+use crate::sampling;
+use crate::scene::Scene;
+use crate::settings::{RenderSettings, Sampler};
+
+pub struct Pathtracer {
+    width: u32,
+    height: u32,
+    settings: RenderSettings,
+    bvh_stats: SceneBvhStats,
+    // CPU copy of the BVH, its top level is refit or rebuilt when geometry moves
+    bvh: SceneBvh,
+    top_level_built_cost: f32,
+    mesh_bounds: Vec<Aabb>,
+    // Number of samples averaged into accum_buffer so far
+    frame: u32,
+    // Resources
+    display_sampler: wgpu::Sampler,
+    image_buffers: ImageBuffers,
+    scene_buffers: SceneBuffers,
+    camera_buffer: GPUBuffer,
+    params_buffer0: GPUBuffer,
+    params_buffer1: GPUBuffer,
+    blue_noise_buffer: GPUBuffer,
+
+    // Pipelines
+    path_gen_bgl: wgpu::BindGroupLayout,
+    path_gen_bg: wgpu::BindGroup,
+    path_gen_pipeline: wgpu::ComputePipeline,
+    hit_calc_bgl: wgpu::BindGroupLayout,
+    hit_calc_bg: wgpu::BindGroup,
+    hit_calc_pipeline: wgpu::ComputePipeline,
+    shade_bgl: wgpu::BindGroupLayout,
+    shade_bg: wgpu::BindGroup,
+    shade_pipeline: wgpu::ComputePipeline,
+    image_bgl: wgpu::BindGroupLayout,
+    image_bg: wgpu::BindGroup,
+    image_pipeline: wgpu::ComputePipeline,
+}
+
+/// The compute passes a sample goes through, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComputeShader {
+    GeneratePaths,
+    CalculateIntersections,
+    ShadePaths,
+    /// Averages the finished paths into the image
+    Accumulate,
+}
+
+impl ComputeShader {
+    pub const ALL: [Self; 4] = [
+        Self::GeneratePaths,
+        Self::CalculateIntersections,
+        Self::ShadePaths,
+        Self::Accumulate,
+    ];
+
+    /// Name of its GLSL source in `src/shaders`.
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Self::GeneratePaths => "generate_paths.comp",
+            Self::CalculateIntersections => "calculate_intersections.comp",
+            Self::ShadePaths => "shade_paths.comp",
+            Self::Accumulate => "viewer.comp",
+        }
+    }
+
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|shader| shader.file_name() == name)
+    }
+}
+
+/// Everything with one element per pixel, replaced as a whole by `Pathtracer::resize`.
+struct ImageBuffers {
+    display_texture: wgpu::Texture,
+    paths_buffer: GPUBuffer,
+    intersect_buffer: GPUBuffer,
+    // Running sum of every sample per pixel, the sample count is kept in w
+    accum_buffer: GPUBuffer,
+}
+
+/// Everything uploaded from a `Scene`, replaced as a whole by `Pathtracer::set_scene`.
+struct SceneBuffers {
+    geometry_buffer: GPUBuffer,
+    material_buffer: GPUBuffer,
+    vertex_buffer: GPUBuffer,
+    index_buffer: GPUBuffer,
+    mesh_buffer: GPUBuffer,
+    bvh_node_buffer: GPUBuffer,
+    bvh_primitive_buffer: GPUBuffer,
+    texture_info_buffer: GPUBuffer,
+    texel_buffer: GPUBuffer,
+}
+
+impl Pathtracer {
+    /// Limits the device passed to `new` has to be created with. The intersection pass binds
+    /// more storage buffers than wgpu allows by default.
+    pub fn required_limits() -> wgpu::Limits {
+        wgpu::Limits {
+            max_storage_buffers_per_shader_stage: 8,
+            ..wgpu::Limits::default()
+        }
+    }
+
+    pub fn new(
+        device: &wgpu::Device,
+        camera: &Camera,
+        scene: &Scene,
+        settings: RenderSettings,
+    ) -> Self {
+        let width = camera.res_x();
+        let height = camera.res_y();
+
+        // Initialize resources
+        let temp_cam = [*camera];
+        let camera_buf_desc = GPUBufferDescription::<Camera> {
+            contents: Some(&temp_cam),
+            element_count: 1,
+            element_size: std::mem::size_of::<Camera>(),
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        };
+        let camera_buffer = GPUBuffer::new(&device, camera_buf_desc);
+
+        let image_buffers = ImageBuffers::new(device, width, height);
+
+        // Resolution, frame index and padding up to 16 bytes
+        let params0 = [width, height, 0, 0];
+        // TODO: pass this in to renormalize
+        //let look_at = camera.look_at();
+        let params_buf0_des = GPUBufferDescription::<u32> {
+            contents: Some(&params0),
+            element_count: 4,
+            element_size: 4,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        };
+        let params_buffer0 = GPUBuffer::new(&device, params_buf0_des);
+
+        let bvh = SceneBvh::build(scene, &settings.bvh);
+        let bvh_stats = bvh.stats();
+        log::info!("BVH: {}", bvh_stats);
+        let scene_buffers = SceneBuffers::new(device, scene, &bvh);
+        let mesh_bounds = bvh.mesh_bounds();
+        let top_level_built_cost = bvh.top_level.stats.sah_cost;
+
+        // Geometry count, path count, frame index, bounce limit, random seed, sampler, image
+        // width and padding up to 32 bytes
+        let params1 = [
+            scene.geometry.len() as u32,
+            width * height,
+            0,
+            settings.max_bounces,
+            settings.seed,
+            settings.sampler as u32,
+            width,
+            0,
+        ];
+        let params_buf1_desc = GPUBufferDescription::<u32> {
+            contents: Some(&params1),
+            element_count: 8,
+            element_size: 4,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        };
+        let params_buffer1 = GPUBuffer::new(&device, params_buf1_desc);
+
+        // Making the mask takes a moment, the other samplers get a single texel they never read
+        let blue_noise = match settings.sampler {
+            Sampler::BlueNoise => sampling::blue_noise_mask(),
+            _ => vec![0.0],
+        };
+        let blue_noise_buf_desc = GPUBufferDescription::<f32> {
+            contents: Some(&blue_noise),
+            element_count: blue_noise.len() as u32,
+            element_size: std::mem::size_of::<f32>(),
+            usage: wgpu::BufferUsage::STORAGE,
+        };
+        let blue_noise_buffer = GPUBuffer::new(&device, blue_noise_buf_desc);
+
+        let display_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let path_gen_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                camera_buffer.as_bgl_entry(0, wgpu::ShaderStage::COMPUTE, true),
+                image_buffers
+                    .paths_buffer
+                    .as_bgl_entry(1, wgpu::ShaderStage::COMPUTE, false),
+                params_buffer1.as_bgl_entry(2, wgpu::ShaderStage::COMPUTE, true),
+                blue_noise_buffer.as_bgl_entry(3, wgpu::ShaderStage::COMPUTE, true),
+            ],
+        });
+        let path_gen_bg = path_gen_bind_group(
+            device,
+            &path_gen_bgl,
+            &camera_buffer,
+            &image_buffers,
+            &params_buffer1,
+            &blue_noise_buffer,
+        );
+
+        // Getting around https://github.com/gfx-rs/naga/issues/406
+        let path_gen_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("shaders/generate_paths.comp.spv"),
+            source: wgpu::util::make_spirv(include_bytes!("shaders/generate_paths.comp.spv")),
+            flags: std::iter::empty::<wgpu::ShaderFlags>().collect(),
+        });
+        //let path_gen_module = device.create_shader_module(&wgpu::include_spirv!("shaders/generate_paths.comp.spv"));
+        let path_gen_pipeline =
+            compute_pipeline(device, "path_gen_pipeline", &path_gen_bgl, &path_gen_module);
+
+        let hit_calc_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                image_buffers
+                    .intersect_buffer
+                    .as_bgl_entry(0, wgpu::ShaderStage::COMPUTE, false),
+                scene_buffers
+                    .geometry_buffer
+                    .as_bgl_entry(1, wgpu::ShaderStage::COMPUTE, true),
+                image_buffers
+                    .paths_buffer
+                    .as_bgl_entry(2, wgpu::ShaderStage::COMPUTE, true),
+                params_buffer1.as_bgl_entry(3, wgpu::ShaderStage::COMPUTE, true),
+                scene_buffers
+                    .vertex_buffer
+                    .as_bgl_entry(4, wgpu::ShaderStage::COMPUTE, true),
+                scene_buffers
+                    .index_buffer
+                    .as_bgl_entry(5, wgpu::ShaderStage::COMPUTE, true),
+                scene_buffers
+                    .mesh_buffer
+                    .as_bgl_entry(6, wgpu::ShaderStage::COMPUTE, true),
+                scene_buffers
+                    .bvh_node_buffer
+                    .as_bgl_entry(7, wgpu::ShaderStage::COMPUTE, true),
+                scene_buffers.bvh_primitive_buffer.as_bgl_entry(
+                    8,
+                    wgpu::ShaderStage::COMPUTE,
+                    true,
+                ),
+            ],
+        });
+
+        let hit_calc_bg = hit_calc_bind_group(
+            device,
+            &hit_calc_bgl,
+            &image_buffers,
+            &params_buffer1,
+            &scene_buffers,
+        );
+
+        let hit_calc_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::util::make_spirv(include_bytes!(
+                "shaders/calculate_intersections.comp.spv"
+            )),
+            flags: std::iter::empty::<wgpu::ShaderFlags>().collect(),
+        });
+
+        let hit_calc_pipeline =
+            compute_pipeline(device, "hit_calc_pipeline", &hit_calc_bgl, &hit_calc_module);
+
+        let shade_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                image_buffers
+                    .paths_buffer
+                    .as_bgl_entry(0, wgpu::ShaderStage::COMPUTE, false),
+                image_buffers
+                    .intersect_buffer
+                    .as_bgl_entry(1, wgpu::ShaderStage::COMPUTE, true),
+                params_buffer1.as_bgl_entry(2, wgpu::ShaderStage::COMPUTE, true),
+                scene_buffers
+                    .material_buffer
+                    .as_bgl_entry(3, wgpu::ShaderStage::COMPUTE, true),
+                scene_buffers
+                    .texture_info_buffer
+                    .as_bgl_entry(4, wgpu::ShaderStage::COMPUTE, true),
+                scene_buffers
+                    .texel_buffer
+                    .as_bgl_entry(5, wgpu::ShaderStage::COMPUTE, true),
+                blue_noise_buffer.as_bgl_entry(6, wgpu::ShaderStage::COMPUTE, true),
+            ],
+        });
+
+        let shade_bg = shade_bind_group(
+            device,
+            &shade_bgl,
+            &image_buffers,
+            &params_buffer1,
+            &scene_buffers,
+            &blue_noise_buffer,
+        );
+
+        let shade_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("shaders/shade_paths.comp.spv"),
+            source: wgpu::util::make_spirv(include_bytes!("shaders/shade_paths.comp.spv")),
+            flags: std::iter::empty::<wgpu::ShaderFlags>().collect(),
+        });
+
+        let shade_pipeline = compute_pipeline(device, "shade_pipeline", &shade_bgl, &shade_module);
+
+        let image_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: wgpu::TextureFormat::Rgba8Unorm,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::COMPUTE,
+                    ty: wgpu::BindingType::Sampler {
+                        filtering: true,
+                        comparison: false,
+                    },
+                    count: None,
+                },
+                image_buffers
+                    .paths_buffer
+                    .as_bgl_entry(2, wgpu::ShaderStage::COMPUTE, true),
+                params_buffer0.as_bgl_entry(3, wgpu::ShaderStage::COMPUTE, true),
+                image_buffers
+                    .accum_buffer
+                    .as_bgl_entry(4, wgpu::ShaderStage::COMPUTE, false),
+            ],
+        });
+        let image_bg = image_bind_group(
+            device,
+            &image_bgl,
+            &display_sampler,
+            &image_buffers,
+            &params_buffer0,
+        );
+
+        let image_comp_module =
+            device.create_shader_module(&wgpu::include_spirv!("shaders/viewer.comp.spv"));
+        let image_pipeline =
+            compute_pipeline(device, "image_pipeline", &image_bgl, &image_comp_module);
+
+        Self {
+            width,
+            height,
+            settings,
+            bvh_stats,
+            bvh,
+            top_level_built_cost,
+            mesh_bounds,
+            frame: 0,
+            display_sampler,
+            path_gen_bgl,
+            path_gen_bg,
+            path_gen_pipeline,
+            hit_calc_bgl,
+            hit_calc_bg,
+            hit_calc_pipeline,
+            shade_bgl,
+            shade_bg,
+            shade_pipeline,
+            image_bgl,
+            image_bg,
+            image_pipeline,
+            camera_buffer,
+            params_buffer0,
+            params_buffer1,
+            blue_noise_buffer,
+            image_buffers,
+            scene_buffers,
+        }
+    }
+
+    /// Traces one more sample per pixel and averages it into the image.
+    ///
+    /// The frame index goes through `queue`, so the encoder has to be submitted before the
+    /// next call or both samples will see the same index.
+    pub fn run(&mut self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder) {
+        queue.write_buffer(
+            self.params_buffer0.handle(),
+            8,
+            bytemuck::bytes_of(&self.frame),
+        );
+        queue.write_buffer(
+            self.params_buffer1.handle(),
+            8,
+            bytemuck::bytes_of(&self.frame),
+        );
+        self.frame += 1;
+
+        let mut compute_encoder =
+            encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+
+        let block_dims_2d = Vector3::new(
+            (self.width as f32 / 16_f32).ceil() as u32,
+            (self.height as f32 / 16_f32).ceil() as u32,
+            1,
+        );
+
+        let block_dims_1d = Vector3::new(
+            ((self.width * self.height) as f32 / 256f32).ceil() as u32,
+            1,
+            1,
+        );
+
+        compute_encoder.set_pipeline(&self.path_gen_pipeline);
+        compute_encoder.set_bind_group(0, &self.path_gen_bg, &[]);
+        compute_encoder.dispatch(block_dims_2d.x, block_dims_2d.y, block_dims_2d.z);
+
+        // Every bounce finds the closest hits, then shades them and spawns the next rays.
+        // Paths that escaped or ran out of bounces skip both passes.
+        for _ in 0..self.settings.max_bounces {
+            compute_encoder.set_pipeline(&self.hit_calc_pipeline);
+            compute_encoder.set_bind_group(0, &self.hit_calc_bg, &[]);
+            compute_encoder.dispatch(block_dims_1d.x, block_dims_1d.y, block_dims_1d.z);
+
+            compute_encoder.set_pipeline(&self.shade_pipeline);
+            compute_encoder.set_bind_group(0, &self.shade_bg, &[]);
+            compute_encoder.dispatch(block_dims_1d.x, block_dims_1d.y, block_dims_1d.z);
+        }
+
+        compute_encoder.set_pipeline(&self.image_pipeline);
+        compute_encoder.set_bind_group(0, &self.image_bg, &[]);
+        compute_encoder.dispatch(block_dims_2d.x, block_dims_2d.y, block_dims_2d.z);
+        drop(compute_encoder);
+    }
+
+    /// Uploads the geometry moved with `Scene::set_transform` since the last call and refits
+    /// the top level BVH around it, rebuilding it instead once refitting has degraded it past
+    /// `BvhBuildOptions::rebuild_threshold`. Only the changed parts of the buffers are written.
+    ///
+    /// Meshes keep their bottom level trees since those are in object space. Adding or
+    /// removing geometry needs `set_scene`.
+    pub fn apply_edits(&mut self, queue: &wgpu::Queue, scene: &mut Scene) {
+        let edited = scene.take_edits();
+        if edited.is_empty() {
+            return;
+        }
+
+        let geometry_size = std::mem::size_of::<Geometry>();
+        for &index in &edited {
+            queue.write_buffer(
+                self.scene_buffers.geometry_buffer.handle(),
+                (index * geometry_size) as wgpu::BufferAddress,
+                bytemuck::bytes_of(&scene.geometry[index]),
+            );
+        }
+
+        let update = self.bvh.top_level.update_top_level(
+            &scene.geometry,
+            &self.mesh_bounds,
+            self.top_level_built_cost,
+            &self.settings.bvh,
+        );
+        match update {
+            TopLevelUpdate::Rebuilt => {
+                self.top_level_built_cost = self.bvh.top_level.stats.sah_cost;
+                log::debug!("Rebuilt the top level BVH: {}", self.bvh.top_level.stats);
+
+                // Flattening puts the top level first with room for a full rebuild
+                queue.write_buffer(
+                    self.scene_buffers.bvh_node_buffer.handle(),
+                    0,
+                    bytemuck::cast_slice(&self.bvh.top_level.nodes),
+                );
+                queue.write_buffer(
+                    self.scene_buffers.bvh_primitive_buffer.handle(),
+                    0,
+                    bytemuck::cast_slice(&self.bvh.top_level.primitives),
+                );
+            }
+            TopLevelUpdate::Refit(Some(range)) => {
+                let node_size = std::mem::size_of::<BvhNode>();
+                queue.write_buffer(
+                    self.scene_buffers.bvh_node_buffer.handle(),
+                    (range.start * node_size) as wgpu::BufferAddress,
+                    bytemuck::cast_slice(&self.bvh.top_level.nodes[range]),
+                );
+            }
+            TopLevelUpdate::Refit(None) => {}
+        }
+
+        self.bvh_stats.top_level = self.bvh.top_level.stats;
+        self.reset_accumulation();
+    }
+
+    /// Replaces everything that came from the scene: geometry, materials, meshes, textures
+    /// and the BVH over them. Used when the scene changes too much for `apply_edits`, like when
+    /// its file is reloaded.
+    pub fn set_scene(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, scene: &Scene) {
+        let bvh = SceneBvh::build(scene, &self.settings.bvh);
+        self.bvh_stats = bvh.stats();
+        log::info!("BVH: {}", self.bvh_stats);
+        self.scene_buffers = SceneBuffers::new(device, scene, &bvh);
+        self.mesh_bounds = bvh.mesh_bounds();
+        self.top_level_built_cost = bvh.top_level.stats.sah_cost;
+        self.bvh = bvh;
+
+        self.rebuild_scene_bind_groups(device);
+
+        let geometry_count = scene.geometry.len() as u32;
+        queue.write_buffer(
+            self.params_buffer1.handle(),
+            0,
+            bytemuck::bytes_of(&geometry_count),
+        );
+        self.reset_accumulation();
+    }
+
+    /// Changes the image size to the camera's resolution. Everything with one element per
+    /// pixel is allocated again and the accumulation starts over.
+    pub fn resize(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, camera: &Camera) {
+        self.width = camera.res_x();
+        self.height = camera.res_y();
+        self.image_buffers = ImageBuffers::new(device, self.width, self.height);
+
+        self.path_gen_bg = path_gen_bind_group(
+            device,
+            &self.path_gen_bgl,
+            &self.camera_buffer,
+            &self.image_buffers,
+            &self.params_buffer1,
+            &self.blue_noise_buffer,
+        );
+        self.image_bg = image_bind_group(
+            device,
+            &self.image_bgl,
+            &self.display_sampler,
+            &self.image_buffers,
+            &self.params_buffer0,
+        );
+        self.rebuild_scene_bind_groups(device);
+
+        let resolution = [self.width, self.height];
+        queue.write_buffer(
+            self.params_buffer0.handle(),
+            0,
+            bytemuck::bytes_of(&resolution),
+        );
+        let path_count = self.width * self.height;
+        queue.write_buffer(
+            self.params_buffer1.handle(),
+            4,
+            bytemuck::bytes_of(&path_count),
+        );
+        queue.write_buffer(
+            self.params_buffer1.handle(),
+            24,
+            bytemuck::bytes_of(&self.width),
+        );
+        self.update_camera(queue, camera);
+    }
+
+    // The passes that read both the scene and the per pixel buffers
+    fn rebuild_scene_bind_groups(&mut self, device: &wgpu::Device) {
+        self.hit_calc_bg = hit_calc_bind_group(
+            device,
+            &self.hit_calc_bgl,
+            &self.image_buffers,
+            &self.params_buffer1,
+            &self.scene_buffers,
+        );
+        self.shade_bg = shade_bind_group(
+            device,
+            &self.shade_bgl,
+            &self.image_buffers,
+            &self.params_buffer1,
+            &self.scene_buffers,
+            &self.blue_noise_buffer,
+        );
+    }
+
+    /// Builds the pipeline for one of the passes from freshly compiled SPIR-V, to swap in
+    /// with `set_pipeline`. Kept apart so the caller can throw the pipeline away if the device
+    /// reported an error while creating it.
+    pub fn create_pipeline(
+        &self,
+        device: &wgpu::Device,
+        shader: ComputeShader,
+        spirv: &[u8],
+    ) -> wgpu::ComputePipeline {
+        // Same flags `new` creates the modules with, only the accumulation pass gets past
+        // naga's validation (https://github.com/gfx-rs/naga/issues/406)
+        let flags = match shader {
+            ComputeShader::Accumulate => wgpu::ShaderFlags::VALIDATION,
+            _ => wgpu::ShaderFlags::empty(),
+        };
+        let module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some(shader.file_name()),
+            source: wgpu::util::make_spirv(spirv),
+            flags,
+        });
+        let (label, layout) = match shader {
+            ComputeShader::GeneratePaths => ("path_gen_pipeline", &self.path_gen_bgl),
+            ComputeShader::CalculateIntersections => ("hit_calc_pipeline", &self.hit_calc_bgl),
+            ComputeShader::ShadePaths => ("shade_pipeline", &self.shade_bgl),
+            ComputeShader::Accumulate => ("image_pipeline", &self.image_bgl),
+        };
+        compute_pipeline(device, label, layout, &module)
+    }
+
+    /// Replaces the pipeline of one of the passes and starts accumulating from scratch, since
+    /// the old samples came from different code.
+    pub fn set_pipeline(&mut self, shader: ComputeShader, pipeline: wgpu::ComputePipeline) {
+        match shader {
+            ComputeShader::GeneratePaths => self.path_gen_pipeline = pipeline,
+            ComputeShader::CalculateIntersections => self.hit_calc_pipeline = pipeline,
+            ComputeShader::ShadePaths => self.shade_pipeline = pipeline,
+            ComputeShader::Accumulate => self.image_pipeline = pipeline,
+        }
+        self.reset_accumulation();
+    }
+
+    /// Uploads a moved camera and starts accumulating from scratch.
+    pub fn update_camera(&mut self, queue: &wgpu::Queue, camera: &Camera) {
+        queue.write_buffer(self.camera_buffer.handle(), 0, bytemuck::bytes_of(camera));
+        self.reset_accumulation();
+    }
+
+    /// Throws away the accumulated samples, the next `run` overwrites the image.
+    pub fn reset_accumulation(&mut self) {
+        self.frame = 0;
+    }
+
+    /// Number of samples per pixel accumulated so far.
+    pub fn samples(&self) -> u32 {
+        self.frame
+    }
+
+    /// Copies the display texture back to the CPU. Blocks until the GPU is done with it.
+    pub fn read_image(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> image::RgbaImage {
+        // Rows copied out of a texture have to be padded to a 256 byte stride
+        let unpadded_bytes_per_row = self.width * 4;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padding = (align - unpadded_bytes_per_row % align) % align;
+        let padded_bytes_per_row = unpadded_bytes_per_row + padding;
+
+        let readback_buf_desc = GPUBufferDescription::<()> {
+            contents: None,
+            element_count: self.height,
+            element_size: padded_bytes_per_row as usize,
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+        };
+        let readback_buffer = GPUBuffer::new(device, readback_buf_desc);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Readback Encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::TextureCopyView {
+                texture: &self.image_buffers.display_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::BufferCopyView {
+                buffer: readback_buffer.handle(),
+                layout: wgpu::TextureDataLayout {
+                    offset: 0,
+                    bytes_per_row: padded_bytes_per_row,
+                    rows_per_image: self.height,
+                },
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth: 1,
+            },
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let buffer_slice = readback_buffer.contents();
+        let mapping = buffer_slice.map_async(wgpu::MapMode::Read);
+        device.poll(wgpu::Maintain::Wait);
+        futures::executor::block_on(mapping).expect("Failed to map readback buffer");
+
+        // Strip the row padding
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * self.height) as usize);
+        {
+            let padded = buffer_slice.get_mapped_range();
+            for row in padded.chunks(padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+            }
+        }
+        readback_buffer.handle().unmap();
+
+        image::RgbaImage::from_raw(self.width, self.height, pixels)
+            .expect("Readback buffer has the wrong size")
+    }
+
+    /// The BVH over the scene as it is now on the GPU, for tracing rays on the CPU.
+    pub fn bvh(&self) -> &SceneBvh {
+        &self.bvh
+    }
+
+    /// How the BVH built in `new` turned out.
+    pub fn bvh_stats(&self) -> &SceneBvhStats {
+        &self.bvh_stats
+    }
+
+    pub fn settings(&self) -> &RenderSettings {
+        &self.settings
+    }
+
+    /// The image being accumulated. `resize` replaces it, so views of it have to be made
+    /// again afterwards.
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.image_buffers.display_texture
+    }
+
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        &self.display_sampler
+    }
+}
+
+impl ImageBuffers {
+    fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let display_texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsage::SAMPLED
+                | wgpu::TextureUsage::COPY_DST
+                | wgpu::TextureUsage::COPY_SRC
+                | wgpu::TextureUsage::STORAGE,
+            label: Some("display_texture"),
+        });
+
+        let paths_buf_desc = GPUBufferDescription::<()> {
+            contents: None,
+            element_count: width * height,
+            element_size: std::mem::size_of::<PathSegment>(),
+            usage: wgpu::BufferUsage::STORAGE,
+        };
+        let paths_buffer = GPUBuffer::new(device, paths_buf_desc);
+
+        let intersect_buf_desc = GPUBufferDescription::<()> {
+            contents: None,
+            element_count: width * height,
+            element_size: std::mem::size_of::<Intersection>(),
+            usage: wgpu::BufferUsage::STORAGE,
+        };
+        let intersect_buffer = GPUBuffer::new(device, intersect_buf_desc);
+
+        let accum_buf_desc = GPUBufferDescription::<()> {
+            contents: None,
+            element_count: width * height,
+            element_size: std::mem::size_of::<[f32; 4]>(),
+            usage: wgpu::BufferUsage::STORAGE,
+        };
+        let accum_buffer = GPUBuffer::new(device, accum_buf_desc);
+
+        Self {
+            display_texture,
+            paths_buffer,
+            intersect_buffer,
+            accum_buffer,
+        }
+    }
+}
+
+impl SceneBuffers {
+    fn new(device: &wgpu::Device, scene: &Scene, bvh: &SceneBvh) -> Self {
+        // A reloaded scene may well be empty
+        let geometry_buffer = storage_buffer(device, &scene.geometry, wgpu::BufferUsage::COPY_DST);
+        let material_buffer = storage_buffer(device, &scene.materials, wgpu::BufferUsage::empty());
+
+        let flat_bvh = bvh.flatten();
+        let bvh_node_buffer = storage_buffer(device, &flat_bvh.nodes, wgpu::BufferUsage::COPY_DST);
+        let bvh_primitive_buffer =
+            storage_buffer(device, &flat_bvh.primitives, wgpu::BufferUsage::COPY_DST);
+
+        // Every mesh shares one vertex and one index buffer, MeshInfo says where each one starts.
+        // Placing a mesh again only adds a Geometry, its triangles and tree are uploaded once.
+        let mut vertices = Vec::<MeshVertex>::new();
+        let mut indices = Vec::<u32>::new();
+        let mut mesh_infos = Vec::<MeshInfo>::new();
+        for (mesh, &bvh_root) in scene.meshes.iter().zip(&flat_bvh.mesh_roots) {
+            mesh_infos.push(MeshInfo::new(
+                indices.len() as u32,
+                mesh.indices.len() as u32,
+                vertices.len() as u32,
+                bvh_root,
+            ));
+            vertices.extend_from_slice(&mesh.vertices);
+            indices.extend_from_slice(&mesh.indices);
+        }
+        let vertex_buffer = storage_buffer(device, &vertices, wgpu::BufferUsage::empty());
+        let index_buffer = storage_buffer(device, &indices, wgpu::BufferUsage::empty());
+        let mesh_buffer = storage_buffer(device, &mesh_infos, wgpu::BufferUsage::empty());
+
+        // Same for textures, TextureInfo says where each one starts in the texel buffer
+        let mut texels = Vec::<u32>::new();
+        let mut texture_infos = Vec::<TextureInfo>::new();
+        for texture in &scene.textures {
+            texture_infos.push(TextureInfo::new(
+                texels.len() as u32,
+                texture.width,
+                texture.height,
+            ));
+            texels.extend_from_slice(&texture.texels);
+        }
+        let texture_info_buffer =
+            storage_buffer(device, &texture_infos, wgpu::BufferUsage::empty());
+        let texel_buffer = storage_buffer(device, &texels, wgpu::BufferUsage::empty());
+
+        Self {
+            geometry_buffer,
+            material_buffer,
+            vertex_buffer,
+            index_buffer,
+            mesh_buffer,
+            bvh_node_buffer,
+            bvh_primitive_buffer,
+            texture_info_buffer,
+            texel_buffer,
+        }
+    }
+}
+
+fn compute_pipeline(
+    device: &wgpu::Device,
+    label: &str,
+    bind_group_layout: &wgpu::BindGroupLayout,
+    module: &wgpu::ShaderModule,
+) -> wgpu::ComputePipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some(label),
+        layout: Some(&layout),
+        module,
+        entry_point: "main",
+    })
+}
+
+fn path_gen_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    camera_buffer: &GPUBuffer,
+    image_buffers: &ImageBuffers,
+    params_buffer1: &GPUBuffer,
+    blue_noise_buffer: &GPUBuffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("path_gen_bind_group"),
+        layout,
+        entries: &[
+            camera_buffer.as_bg_entry(0),
+            image_buffers.paths_buffer.as_bg_entry(1),
+            params_buffer1.as_bg_entry(2),
+            blue_noise_buffer.as_bg_entry(3),
+        ],
+    })
+}
+
+fn hit_calc_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    image_buffers: &ImageBuffers,
+    params_buffer1: &GPUBuffer,
+    scene_buffers: &SceneBuffers,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout,
+        entries: &[
+            image_buffers.intersect_buffer.as_bg_entry(0),
+            scene_buffers.geometry_buffer.as_bg_entry(1),
+            image_buffers.paths_buffer.as_bg_entry(2),
+            params_buffer1.as_bg_entry(3),
+            scene_buffers.vertex_buffer.as_bg_entry(4),
+            scene_buffers.index_buffer.as_bg_entry(5),
+            scene_buffers.mesh_buffer.as_bg_entry(6),
+            scene_buffers.bvh_node_buffer.as_bg_entry(7),
+            scene_buffers.bvh_primitive_buffer.as_bg_entry(8),
+        ],
+    })
+}
+
+fn shade_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    image_buffers: &ImageBuffers,
+    params_buffer1: &GPUBuffer,
+    scene_buffers: &SceneBuffers,
+    blue_noise_buffer: &GPUBuffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("shade_bind_group"),
+        layout,
+        entries: &[
+            image_buffers.paths_buffer.as_bg_entry(0),
+            image_buffers.intersect_buffer.as_bg_entry(1),
+            params_buffer1.as_bg_entry(2),
+            scene_buffers.material_buffer.as_bg_entry(3),
+            scene_buffers.texture_info_buffer.as_bg_entry(4),
+            scene_buffers.texel_buffer.as_bg_entry(5),
+            blue_noise_buffer.as_bg_entry(6),
+        ],
+    })
+}
+
+fn image_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    image_buffers: &ImageBuffers,
+    params_buffer0: &GPUBuffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("image_bind_group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(
+                    &image_buffers
+                        .display_texture
+                        .create_view(&wgpu::TextureViewDescriptor::default()),
+                ),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+            image_buffers.paths_buffer.as_bg_entry(2),
+            params_buffer0.as_bg_entry(3),
+            image_buffers.accum_buffer.as_bg_entry(4),
+        ],
+    })
+}
+
+/// Storage buffer holding `contents`, with any `extra_usage` on top. Bindings can't be empty,
+/// so an empty slice uploads a single zeroed element instead.
+fn storage_buffer<T: Pod + Zeroable>(
+    device: &wgpu::Device,
+    contents: &[T],
+    extra_usage: wgpu::BufferUsage,
+) -> GPUBuffer {
+    let zeroed = [T::zeroed()];
+    let contents = if contents.is_empty() {
+        &zeroed[..]
+    } else {
+        contents
+    };
+    let buf_desc = GPUBufferDescription::<T> {
+        contents: Some(contents),
+        element_count: contents.len() as u32,
+        element_size: std::mem::size_of::<T>(),
+        usage: wgpu::BufferUsage::STORAGE | extra_usage,
+    };
+    GPUBuffer::new(device, buf_desc)
+}
@@ -0,0 +1,461 @@
+//! CPU side of the sample generators in `shaders/include/sampling.glsl`. The pathtracer only
+//! needs the blue noise mask from here, the sequences mirror the shader code so the tests can
+//! check that they stay stratified.
+
+/// Width and height of the blue noise mask, which tiles the image.
+pub const BLUE_NOISE_SIZE: usize = 64;
+
+/// Bases for the Halton dimensions, which wrap around past the last one.
+pub const HALTON_PRIMES: [u32; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311,
+];
+
+/// Generator matrices of the first four Sobol dimensions, one column per index bit. Higher
+/// dimensions reuse them with a different scramble.
+pub const SOBOL_DIRECTIONS: [[u32; 32]; 4] = sobol_directions();
+
+/// Joe and Kuo's primitive polynomials and initial direction numbers for dimensions 1 to 3,
+/// as (degree, coefficients, initial numbers). Dimension 0 is the van der Corput sequence.
+const SOBOL_POLYNOMIALS: [(usize, u32, [u32; 3]); 3] =
+    [(1, 0, [1, 0, 0]), (2, 1, [1, 3, 0]), (3, 1, [1, 3, 1])];
+
+const fn sobol_directions() -> [[u32; 32]; 4] {
+    let mut directions = [[0; 32]; 4];
+    let mut bit = 0;
+    while bit < 32 {
+        directions[0][bit] = 1 << (31 - bit);
+        bit += 1;
+    }
+
+    let mut dimension = 1;
+    while dimension < 4 {
+        let (degree, coefficients, initial) = SOBOL_POLYNOMIALS[dimension - 1];
+        let mut bit = 0;
+        while bit < 32 {
+            let v = &directions[dimension];
+            let mut x;
+            if bit < degree {
+                x = initial[bit] << (31 - bit);
+            } else {
+                x = v[bit - degree] ^ (v[bit - degree] >> degree);
+                let mut k = 1;
+                while k < degree {
+                    if (coefficients >> (degree - 1 - k)) & 1 == 1 {
+                        x ^= v[bit - k];
+                    }
+                    k += 1;
+                }
+            }
+            directions[dimension][bit] = x;
+            bit += 1;
+        }
+        dimension += 1;
+    }
+    directions
+}
+
+/// PCG-RXS-M-XS, same as `pcg_hash` in `math.glsl`.
+pub fn pcg_hash(v: u32) -> u32 {
+    let state = v.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+fn hash_combine(seed: u32, v: u32) -> u32 {
+    seed ^ pcg_hash(v).wrapping_add(seed << 6).wrapping_add(seed >> 2)
+}
+
+/// The top 24 bits as a float in [0, 1), any more could round up to 1.0.
+fn to_unit(x: u32) -> f32 {
+    (x >> 8) as f32 / 16777216.0
+}
+
+/// Point `index` of one of the first four Sobol dimensions, as a 0.32 fixed point number.
+pub fn sobol(mut index: u32, dimension: usize) -> u32 {
+    let mut x = 0;
+    for &direction in &SOBOL_DIRECTIONS[dimension] {
+        if index == 0 {
+            break;
+        }
+        if index & 1 == 1 {
+            x ^= direction;
+        }
+        index >>= 1;
+    }
+    x
+}
+
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
+
+/// Owen scrambling: every bit gets flipped depending on the bits above it, which keeps
+/// points in the same binary intervals together.
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+/// Owen-scrambled Sobol, shuffled and padded in groups of four dimensions (Burley, "Practical
+/// Hash-based Owen Scrambling", 2020).
+pub fn sobol_owen(index: u32, dimension: u32, seed: u32) -> f32 {
+    let group_seed = hash_combine(seed, dimension / 4);
+    let shuffled = nested_uniform_scramble(index, group_seed);
+    let x = sobol(shuffled, (dimension % 4) as usize);
+    to_unit(nested_uniform_scramble(
+        x,
+        hash_combine(group_seed, dimension % 4),
+    ))
+}
+
+/// `index` with its digits in `base` mirrored around the point.
+pub fn radical_inverse(base: u32, mut index: u32) -> f32 {
+    let inverse_base = 1.0 / base as f32;
+    let mut digit_value = inverse_base;
+    let mut x = 0.0;
+    while index > 0 {
+        x += (index % base) as f32 * digit_value;
+        index /= base;
+        digit_value *= inverse_base;
+    }
+    x
+}
+
+/// Halton, shifted around the unit interval by a per pixel hash so neighbouring pixels don't
+/// see the same points.
+pub fn halton(index: u32, dimension: u32, seed: u32) -> f32 {
+    let base = HALTON_PRIMES[dimension as usize % HALTON_PRIMES.len()];
+    let shift = to_unit(hash_combine(seed, dimension));
+    (radical_inverse(base, index) + shift).fract()
+}
+
+/// A tileable mask where each value is about as far as possible from similar ones, made
+/// with Ulichney's void and cluster method. Values are evenly spread over [0, 1) in
+/// row-major order.
+pub fn blue_noise_mask() -> Vec<f32> {
+    const SIGMA: f32 = 1.5;
+    let size = BLUE_NOISE_SIZE;
+    let count = size * size;
+
+    let mut pattern = Pattern {
+        points: vec![false; count],
+        energy: vec![0.0; count],
+        // The Gaussian every point spreads around itself, wrapped around the edges
+        kernel: (0..count)
+            .map(|i| {
+                let wrapped = |d: usize| d.min(size - d) as f32;
+                let (dx, dy) = (wrapped(i % size), wrapped(i / size));
+                (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp()
+            })
+            .collect(),
+    };
+
+    // Start from a tenth of the pixels picked at random, then keep moving the point in the
+    // tightest cluster into the largest void until that's where it came from
+    let mut state = 0;
+    let mut placed = 0;
+    while placed < count / 10 {
+        state = pcg_hash(state);
+        let i = state as usize % count;
+        if !pattern.points[i] {
+            pattern.set(i, true);
+            placed += 1;
+        }
+    }
+    loop {
+        let cluster = pattern.tightest_cluster();
+        pattern.set(cluster, false);
+        let void = pattern.largest_void();
+        pattern.set(void, true);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0; count];
+    // The initial points get the lowest ranks, taking the tightest cluster out first
+    let initial = pattern.points.clone();
+    for rank in (0..placed).rev() {
+        let cluster = pattern.tightest_cluster();
+        pattern.set(cluster, false);
+        ranks[cluster] = rank;
+    }
+    for (i, &point) in initial.iter().enumerate() {
+        if point {
+            pattern.set(i, true);
+        }
+    }
+    // The rest fill the largest void left each time
+    for rank in placed..count {
+        let void = pattern.largest_void();
+        pattern.set(void, true);
+        ranks[void] = rank;
+    }
+
+    ranks
+        .into_iter()
+        .map(|rank| (rank as f32 + 0.5) / count as f32)
+        .collect()
+}
+
+/// Points on the mask grid and how crowded each pixel is by them.
+struct Pattern {
+    points: Vec<bool>,
+    energy: Vec<f32>,
+    kernel: Vec<f32>,
+}
+
+impl Pattern {
+    fn set(&mut self, i: usize, point: bool) {
+        self.points[i] = point;
+        let sign = if point { 1.0 } else { -1.0 };
+        let size = BLUE_NOISE_SIZE;
+        let (x, y) = (i % size, i / size);
+        for (j, energy) in self.energy.iter_mut().enumerate() {
+            let dx = (j % size + size - x) % size;
+            let dy = (j / size + size - y) % size;
+            *energy += sign * self.kernel[dx + dy * size];
+        }
+    }
+
+    /// The point with the most others around it.
+    fn tightest_cluster(&self) -> usize {
+        self.extreme(true, |a, b| a > b)
+    }
+
+    /// The empty pixel with the fewest points around it.
+    fn largest_void(&self) -> usize {
+        self.extreme(false, |a, b| a < b)
+    }
+
+    fn extreme(&self, point: bool, better: impl Fn(f32, f32) -> bool) -> usize {
+        let mut best = None;
+        for (i, &energy) in self.energy.iter().enumerate() {
+            if self.points[i] != point {
+                continue;
+            }
+            match best {
+                Some((_, best_energy)) if !better(energy, best_energy) => {}
+                _ => best = Some((i, energy)),
+            }
+        }
+        best.unwrap().0
+    }
+}
+
+/// Checks that the samplers spread their points the way they're meant to, and that the tables
+/// they use are the same as in the shader header. A broken sampler still renders, it just
+/// converges slower or shows patterns, which is easy to miss.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = include_str!("shaders/include/sampling.glsl");
+
+    // Scrambles to try, every one of them has to be stratified
+    const SEEDS: [u32; 4] = [0, 1, 0x9e3779b9, 0xdeadbeef];
+
+    /// The shader's copy of a table, read out of its array initializer in the header.
+    fn glsl_array(name: &str) -> Vec<u32> {
+        let start = HEADER
+            .find(&format!("const uint {}[", name))
+            .unwrap_or_else(|| panic!("No {} in sampling.glsl", name));
+        let body = &HEADER[start..];
+        let body = &body[body.find('(').expect("No initializer") + 1..];
+        let body = &body[..body.find(')').expect("Unterminated initializer")];
+        body.split(',')
+            .map(|number| {
+                let number = number.trim().trim_end_matches('u');
+                match number.strip_prefix("0x") {
+                    Some(hex) => u32::from_str_radix(hex, 16),
+                    None => number.parse(),
+                }
+                .unwrap_or_else(|_| panic!("Bad number {:?} in {}", number, name))
+            })
+            .collect()
+    }
+
+    fn stratified_1d(points: impl Iterator<Item = f32>, intervals: u32) -> bool {
+        let mut seen = vec![false; intervals as usize];
+        for x in points {
+            let i = ((x * intervals as f32) as usize).min(seen.len() - 1);
+            if std::mem::replace(&mut seen[i], true) {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn stratified_2d(points: &[(f32, f32)], cells: (u32, u32)) -> bool {
+        let mut seen = vec![false; (cells.0 * cells.1) as usize];
+        for &(x, y) in points {
+            let cx = ((x * cells.0 as f32) as u32).min(cells.0 - 1);
+            let cy = ((y * cells.1 as f32) as u32).min(cells.1 - 1);
+            if std::mem::replace(&mut seen[(cy * cells.0 + cx) as usize], true) {
+                return false;
+            }
+        }
+        true
+    }
+
+    // Undoes the per pixel Halton shift, which moves the intervals along with the points. It's
+    // nudged half a step in so points on an interval's edge don't get counted in the one
+    // before because of rounding.
+    fn unshifted(x: f32, shift: f32) -> f32 {
+        (x - shift + 1e-4).rem_euclid(1.0)
+    }
+
+    #[test]
+    fn tables_match_the_shader() {
+        let sobol: Vec<u32> = SOBOL_DIRECTIONS.iter().flatten().copied().collect();
+        assert_eq!(glsl_array("SOBOL_DIRECTIONS"), sobol);
+        assert_eq!(glsl_array("HALTON_PRIMES"), HALTON_PRIMES);
+    }
+
+    /// Every power of two prefix has one point in each interval of its size.
+    #[test]
+    fn sobol_stratified() {
+        for &seed in &SEEDS {
+            for dimension in 0..8 {
+                for log_count in 0..=8 {
+                    let points = (0..1 << log_count).map(|i| sobol_owen(i, dimension, seed));
+                    assert!(
+                        stratified_1d(points, 1 << log_count),
+                        "Sobol dimension {} with seed {:#x}: first {} points",
+                        dimension,
+                        seed,
+                        1 << log_count
+                    );
+                }
+            }
+        }
+    }
+
+    /// The first two dimensions of every group of four form a (0, 2)-sequence, stratified in
+    /// every rectangle shape with that area.
+    #[test]
+    fn sobol_pairs_stratified() {
+        for &seed in &SEEDS {
+            for &dimension in &[0, 4] {
+                for log_count in 0..=8 {
+                    let points: Vec<_> = (0..1 << log_count)
+                        .map(|i| {
+                            (
+                                sobol_owen(i, dimension, seed),
+                                sobol_owen(i, dimension + 1, seed),
+                            )
+                        })
+                        .collect();
+                    for log_x in 0..=log_count {
+                        let cells = (1 << log_x, 1 << (log_count - log_x));
+                        assert!(
+                            stratified_2d(&points, cells),
+                            "Sobol dimensions {} and {} with seed {:#x}: first {} points in {}x{} cells",
+                            dimension,
+                            dimension + 1,
+                            seed,
+                            points.len(),
+                            cells.0,
+                            cells.1
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// The first `base^k` points of a dimension land one per interval.
+    #[test]
+    fn halton_stratified() {
+        for &seed in &SEEDS {
+            for dimension in 0..HALTON_PRIMES.len() as u32 {
+                let base = HALTON_PRIMES[dimension as usize];
+                let shift = halton(0, dimension, seed);
+                let mut count = 1;
+                while count <= 4096 {
+                    let points = (0..count).map(|i| unshifted(halton(i, dimension, seed), shift));
+                    assert!(
+                        stratified_1d(points, count),
+                        "Halton dimension {} with seed {:#x}: first {} points",
+                        dimension,
+                        seed,
+                        count
+                    );
+                    count *= base;
+                }
+            }
+        }
+    }
+
+    /// The first `2^a * 3^b` points of the first two dimensions land one per cell of that grid.
+    #[test]
+    fn halton_pairs_stratified() {
+        for &seed in &SEEDS {
+            let shift = (halton(0, 0, seed), halton(0, 1, seed));
+            for &cells in &[(2, 3), (4, 3), (8, 9), (16, 27)] {
+                let points: Vec<_> = (0..cells.0 * cells.1)
+                    .map(|i| {
+                        (
+                            unshifted(halton(i, 0, seed), shift.0),
+                            unshifted(halton(i, 1, seed), shift.1),
+                        )
+                    })
+                    .collect();
+                assert!(
+                    stratified_2d(&points, cells),
+                    "Halton dimensions 0 and 1 with seed {:#x}: {}x{} cells",
+                    seed,
+                    cells.0,
+                    cells.1
+                );
+            }
+        }
+    }
+
+    /// Every value of the mask shows up exactly once, and the values even out over small
+    /// windows. White noise averages about 0.036 away from 0.5 over 8x8 windows.
+    #[test]
+    fn blue_noise_stratified() {
+        const WINDOW: usize = 8;
+        const MAX_WINDOW_ERROR: f32 = 0.015;
+
+        let mask = blue_noise_mask();
+        let count = BLUE_NOISE_SIZE * BLUE_NOISE_SIZE;
+        assert_eq!(mask.len(), count);
+        assert!(
+            stratified_1d(mask.iter().copied(), count as u32),
+            "Blue noise mask doesn't use every value once"
+        );
+
+        // Root mean square of how far each window's average is from 0.5
+        let mut squared_error = 0.0;
+        for y in 0..BLUE_NOISE_SIZE {
+            for x in 0..BLUE_NOISE_SIZE {
+                let mut sum = 0.0;
+                for dy in 0..WINDOW {
+                    for dx in 0..WINDOW {
+                        let (wx, wy) = ((x + dx) % BLUE_NOISE_SIZE, (y + dy) % BLUE_NOISE_SIZE);
+                        sum += mask[wy * BLUE_NOISE_SIZE + wx];
+                    }
+                }
+                let error = sum / (WINDOW * WINDOW) as f32 - 0.5;
+                squared_error += error * error;
+            }
+        }
+        let window_error = (squared_error / count as f32).sqrt();
+        assert!(
+            window_error <= MAX_WINDOW_ERROR,
+            "Blue noise mask averages {} off over {}x{} windows, too close to white noise",
+            window_error,
+            WINDOW,
+            WINDOW
+        );
+    }
+}
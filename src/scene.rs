@@ -0,0 +1,158 @@
+use super::data_types::{GeomType, Geometry, Material};
+use super::mesh::Mesh;
+use super::texture::Texture;
+use cgmath::Matrix4;
+
+#[repr(C)]
+pub struct Scene {
+    //pub camera: Camera,
+    pub geometry: Vec<Geometry>,
+    pub materials: Vec<Material>,
+    pub meshes: Vec<Mesh>,
+    pub textures: Vec<Texture>,
+    // Geometry moved since the last `take_edits`
+    edited: Vec<usize>,
+}
+
+impl Scene {
+    /// Builds the default scene: a diffuse, a glass and a metal sphere resting on a much
+    /// larger one.
+    pub fn new() -> Self {
+        let mut scene = Self::empty();
+        let ground = scene.add_material(Material::lambertian([0.5, 0.5, 0.5]));
+        let diffuse = scene.add_material(Material::lambertian([0.7, 0.3, 0.3]));
+        let glass = scene.add_material(Material::dielectric(1.5));
+        let metal = scene.add_material(Material::metal([0.8, 0.6, 0.2], 0.1));
+
+        let s_mat = Matrix4::from_scale(1f32);
+        for (x, material) in [(0f32, diffuse), (-2.1, glass), (2.1, metal)].iter() {
+            let t_mat = Matrix4::from_translation(cgmath::vec3(*x, 0f32, 3f32));
+            scene.add_geometry(Geometry::new(GeomType::SPHERE, t_mat * s_mat, *material));
+        }
+
+        let ground_transf = Matrix4::from_translation(cgmath::vec3(0f32, -101f32, 3f32))
+            * Matrix4::from_scale(100f32);
+        scene.add_geometry(Geometry::new(GeomType::SPHERE, ground_transf, ground));
+        scene
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            geometry: Vec::new(),
+            materials: Vec::new(),
+            meshes: Vec::new(),
+            textures: Vec::new(),
+            edited: Vec::new(),
+        }
+    }
+
+    /// Adds a material and returns the id geometry uses to refer to it.
+    pub fn add_material(&mut self, material: Material) -> u32 {
+        self.materials.push(material);
+        (self.materials.len() - 1) as u32
+    }
+
+    /// Adds a mesh and returns the id to place it with `Geometry::mesh`. The same mesh can be
+    /// placed any number of times.
+    pub fn add_mesh(&mut self, mesh: Mesh) -> u32 {
+        self.meshes.push(mesh);
+        (self.meshes.len() - 1) as u32
+    }
+
+    /// Adds a texture and returns the id materials use to refer to it.
+    pub fn add_texture(&mut self, texture: Texture) -> u32 {
+        self.textures.push(texture);
+        (self.textures.len() - 1) as u32
+    }
+
+    /// Adds a piece of geometry and returns its index.
+    pub fn add_geometry(&mut self, geometry: Geometry) -> usize {
+        self.geometry.push(geometry);
+        self.geometry.len() - 1
+    }
+
+    /// Moves a piece of geometry and remembers it was edited, so `Pathtracer::apply_edits`
+    /// can update the GPU copy and refit the BVH.
+    pub fn set_transform(&mut self, index: usize, transform: Matrix4<f32>) {
+        self.geometry[index].set_transform(transform);
+        self.edited.push(index);
+    }
+
+    /// Moves every piece of geometry to where it is in `other`, if that is all that differs
+    /// between the two scenes. Otherwise returns false and leaves this scene alone.
+    pub fn match_transforms(&mut self, other: &Scene) -> bool {
+        let same_geometry = self.geometry.len() == other.geometry.len()
+            && self.geometry.iter().zip(&other.geometry).all(|(a, b)| {
+                a.ty == b.ty && a.material_id == b.material_id && a.mesh_id == b.mesh_id
+            });
+        if !same_geometry
+            || self.materials != other.materials
+            || self.meshes != other.meshes
+            || self.textures != other.textures
+        {
+            return false;
+        }
+
+        for (index, geometry) in other.geometry.iter().enumerate() {
+            if geometry.transf != self.geometry[index].transf {
+                self.set_transform(index, Matrix4::from(geometry.transf));
+            }
+        }
+        true
+    }
+
+    /// Indices of the geometry edited since the last call, sorted and without repeats.
+    pub fn take_edits(&mut self) -> Vec<usize> {
+        let mut edited = std::mem::take(&mut self.edited);
+        edited.sort_unstable();
+        edited.dedup();
+        edited
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_transforms_moves_geometry() {
+        let mut scene = Scene::new();
+        let mut moved = Scene::new();
+        let transform = Matrix4::from_translation(cgmath::vec3(0.0, 1.0, 3.0));
+        moved.geometry[1].set_transform(transform);
+
+        assert!(scene.match_transforms(&moved));
+        assert_eq!(scene.take_edits(), vec![1]);
+        assert_eq!(scene.geometry[1].transf, moved.geometry[1].transf);
+        assert_eq!(scene.geometry[1].inverse, moved.geometry[1].inverse);
+
+        // Nothing left to move
+        assert!(scene.match_transforms(&moved));
+        assert!(scene.take_edits().is_empty());
+    }
+
+    #[test]
+    fn match_transforms_needs_same_scene() {
+        let mut scene = Scene::new();
+
+        let mut recolored = Scene::new();
+        recolored.materials[1] = Material::lambertian([0.3, 0.3, 0.7]);
+        let mut grown = Scene::new();
+        grown.add_geometry(Geometry::new(GeomType::BOX, Matrix4::from_scale(1.0), 0));
+        let mut swapped = Scene::new();
+        swapped.geometry[0].material_id = 2;
+        swapped.geometry[2].set_transform(Matrix4::from_scale(2.0));
+
+        for other in &[recolored, grown, swapped] {
+            assert!(!scene.match_transforms(other));
+        }
+        assert!(scene.take_edits().is_empty());
+        assert_eq!(scene.geometry[2].transf, Scene::new().geometry[2].transf);
+    }
+}
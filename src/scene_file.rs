@@ -0,0 +1,692 @@
+//! Scene description files, so scenes can live in version control instead of Rust code.
+//!
+//! Scenes are written in [RON](https://github.com/ron-rs/ron). Every section is optional:
+//! ```ron
+//! Scene(
+//!     // `fovy` is half of the vertical field of view in degrees, like `Camera::looking_at`.
+//!     // An `aperture` (the lens radius) blurs everything but what's `focus_distance` ahead,
+//!     // which defaults to the distance to the target.
+//!     camera: (position: (0, 1, -4), target: (0, 0, 3), up: (0, 1, 0), fovy: 30, aperture: 0.05),
+//!     // Or `Orthographic`, `Equirectangular` or `Fisheye` instead of the default `Perspective`
+//!     // camera: (position: (0, 1, -4), target: (0, 0, 3), projection: Fisheye, fovy: 90),
+//!     // Command line flags take precedence over these. `sampler` can also be `Random`,
+//!     // `Halton` or `BlueNoise`.
+//!     settings: (
+//!         width: 1280, height: 720, samples_per_pixel: 64, max_bounces: 8, seed: 7, sampler: Sobol,
+//!     ),
+//!     materials: {
+//!         "ground": Lambertian(albedo: (0.5, 0.5, 0.5)),
+//!         "wood": Textured(texture: "textures/wood.png"),
+//!         "gold": Metal(albedo: (0.8, 0.6, 0.2), roughness: 0.1),
+//!         "glass": Dielectric(ior: 1.5),
+//!     },
+//!     objects: [
+//!         (shape: Sphere, material: "glass", translate: (0, 0, 3)),
+//!         (shape: Sphere, material: "ground", translate: (0, -101, 3), scale: 100),
+//!         (shape: Box, material: "wood", rotate: (0, 45, 0), scale: (2, 0.5, 1)),
+//!         // Meshes keep the materials from their file unless one is given
+//!         (shape: Mesh("models/bunny.obj"), material: "gold"),
+//!     ],
+//!     lights: [
+//!         (shape: Sphere, emission: (4, 4, 4), translate: (0, 5, 3)),
+//!     ],
+//! )
+//! ```
+//! Rotations are in degrees around x, then y, then z. Paths are relative to the scene file.
+//! Meshes can be OBJ or glTF files, and the first glTF camera is used if the scene file
+//! doesn't place one.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use cgmath::{Deg, Matrix4, Point3, Vector3};
+use serde::de::{MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+
+use crate::camera::{Camera, Projection};
+use crate::data_types::{GeomType, Geometry, Material};
+use crate::import;
+use crate::scene::Scene;
+use crate::settings::Sampler;
+use crate::texture::Texture;
+
+/// Everything a scene description file sets up.
+pub struct SceneFile {
+    pub scene: Scene,
+    pub camera: CameraDescription,
+    /// Settings the file asks for, anything it leaves out is up to the caller
+    pub settings: SettingsDescription,
+    /// The scene file itself followed by every mesh and texture it names, to know when it
+    /// needs reloading
+    pub dependencies: Vec<PathBuf>,
+}
+
+/// Camera placement from the file. The resolution is up to the caller, so this only becomes
+/// a `Camera` once it is known.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraDescription {
+    pub position: [f32; 3],
+    pub target: [f32; 3],
+    pub up: [f32; 3],
+    /// Half of the vertical field of view in degrees, like `Camera::looking_at` takes it
+    pub fovy: f32,
+    /// Lens radius, 0 keeps the camera a pinhole with everything in focus
+    pub aperture: f32,
+    /// Distance ahead of the camera that's in focus, the target's if not given
+    #[serde(deserialize_with = "some")]
+    pub focus_distance: Option<f32>,
+    pub projection: Projection,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SettingsDescription {
+    #[serde(default, deserialize_with = "some")]
+    pub width: Option<u32>,
+    #[serde(default, deserialize_with = "some")]
+    pub height: Option<u32>,
+    #[serde(default, deserialize_with = "some")]
+    pub samples_per_pixel: Option<u32>,
+    #[serde(default, deserialize_with = "some")]
+    pub max_bounces: Option<u32>,
+    #[serde(default, deserialize_with = "some")]
+    pub seed: Option<u32>,
+    #[serde(default, deserialize_with = "some")]
+    pub sampler: Option<Sampler>,
+}
+
+impl SceneFile {
+    /// Loads a scene description file, along with the meshes and textures it refers to.
+    ///
+    /// Errors point at the offending spot as `path:line:column`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Unable to read {}", path.display()))?;
+        Self::from_source(path, &source)
+    }
+
+    /// Builds the scene from the file's contents. `path` is only used in errors and to find
+    /// the files the scene refers to.
+    fn from_source(path: &Path, source: &str) -> Result<Self> {
+        let description = parse(source)
+            .map_err(|(location, e)| anyhow!("{}:{}: {}", path.display(), location, e.code))?;
+
+        let mut builder = Builder {
+            path,
+            base_dir: path.parent().unwrap_or_else(|| Path::new("")),
+            source,
+            scene: Scene::empty(),
+            materials: HashMap::new(),
+            default_material: None,
+            imported_camera: None,
+            dependencies: vec![path.to_path_buf()],
+        };
+        for (name, material) in &description.materials {
+            builder.add_material(name, material)?;
+        }
+        for object in &description.objects {
+            builder.add_object(object)?;
+        }
+        for light in &description.lights {
+            builder.add_light(light)?;
+        }
+
+        Ok(Self {
+            camera: description
+                .camera
+                .or(builder.imported_camera)
+                .unwrap_or_default(),
+            settings: description.settings,
+            scene: builder.scene,
+            dependencies: builder.dependencies,
+        })
+    }
+}
+
+impl Default for SceneFile {
+    /// The built-in scene, seen by the default camera.
+    fn default() -> Self {
+        Self {
+            scene: Scene::new(),
+            camera: CameraDescription::default(),
+            settings: SettingsDescription::default(),
+            dependencies: Vec::new(),
+        }
+    }
+}
+
+impl CameraDescription {
+    pub fn camera(&self, width: u32, height: u32) -> Camera {
+        let camera = Camera::looking_at(
+            width,
+            height,
+            Point3::from(self.position),
+            Point3::from(self.target),
+            Vector3::from(self.up),
+            self.fovy,
+        );
+        let focus_distance = self
+            .focus_distance
+            .unwrap_or_else(|| camera.focus_distance());
+        camera
+            .with_thin_lens(self.aperture, focus_distance)
+            .with_projection(self.projection)
+    }
+
+    /// Swaps in `projection` if there is one, for command line flags that override the file.
+    pub fn with_projection(self, projection: Option<Projection>) -> Self {
+        Self {
+            projection: projection.unwrap_or(self.projection),
+            ..self
+        }
+    }
+}
+
+impl Default for CameraDescription {
+    /// Where `Camera::new` puts it.
+    fn default() -> Self {
+        Self {
+            position: [0.0, 0.0, 0.0],
+            target: [0.0, 0.0, 1.0],
+            up: [0.0, 1.0, 0.0],
+            fovy: 45.0,
+            aperture: 0.0,
+            focus_distance: None,
+            projection: Projection::Perspective,
+        }
+    }
+}
+
+impl From<import::gltf::ImportedCamera> for CameraDescription {
+    fn from(camera: import::gltf::ImportedCamera) -> Self {
+        Self {
+            position: camera.position.into(),
+            target: camera.target.into(),
+            up: camera.up.into(),
+            fovy: camera.fovy,
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Scene", deny_unknown_fields)]
+struct SceneDescription<'a> {
+    #[serde(default, deserialize_with = "some")]
+    camera: Option<CameraDescription>,
+    #[serde(default)]
+    settings: SettingsDescription,
+    #[serde(default, borrow, deserialize_with = "ordered_map")]
+    materials: Vec<(Text<'a>, MaterialDescription<'a>)>,
+    #[serde(default, borrow)]
+    objects: Vec<ObjectDescription<'a>>,
+    #[serde(default, borrow)]
+    lights: Vec<LightDescription<'a>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+enum MaterialDescription<'a> {
+    Lambertian {
+        albedo: [f32; 3],
+    },
+    Textured {
+        #[serde(borrow)]
+        texture: Text<'a>,
+    },
+    Metal {
+        albedo: [f32; 3],
+        #[serde(default)]
+        roughness: f32,
+    },
+    Dielectric {
+        ior: f32,
+    },
+    Emissive {
+        emission: [f32; 3],
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ObjectDescription<'a> {
+    #[serde(borrow)]
+    shape: Shape<'a>,
+    /// Meshes without one keep the materials from their file, other shapes get a grey one
+    #[serde(default, borrow, deserialize_with = "some")]
+    material: Option<Text<'a>>,
+    #[serde(default)]
+    translate: [f32; 3],
+    #[serde(default)]
+    rotate: [f32; 3],
+    #[serde(default)]
+    scale: Scale,
+}
+
+/// Geometry with its own emissive material, since that's all a light is to the pathtracer.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LightDescription<'a> {
+    #[serde(borrow)]
+    shape: Shape<'a>,
+    emission: [f32; 3],
+    #[serde(default)]
+    translate: [f32; 3],
+    #[serde(default)]
+    rotate: [f32; 3],
+    #[serde(default)]
+    scale: Scale,
+}
+
+#[derive(Deserialize)]
+enum Shape<'a> {
+    /// Unit radius, centered on the origin
+    Sphere,
+    /// Unit size, centered on the origin
+    Box,
+    Mesh(#[serde(borrow)] Text<'a>),
+}
+
+/// Either a single factor or one per axis. Zero is rejected here rather than when the
+/// transform gets inverted, so the error still knows where it came from.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(try_from = "ScaleValue")]
+struct Scale([f32; 3]);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScaleValue {
+    Uniform(f32),
+    PerAxis([f32; 3]),
+}
+
+impl TryFrom<ScaleValue> for Scale {
+    type Error = &'static str;
+
+    fn try_from(value: ScaleValue) -> Result<Self, Self::Error> {
+        let scale = match value {
+            ScaleValue::Uniform(s) => [s; 3],
+            ScaleValue::PerAxis(scale) => scale,
+        };
+        if scale.contains(&0.0) {
+            Err("scale can't be zero")
+        } else {
+            Ok(Self(scale))
+        }
+    }
+}
+
+impl Default for Scale {
+    fn default() -> Self {
+        Self([1.0; 3])
+    }
+}
+
+/// A string from the file. It is borrowed whenever it has no escapes, so errors about it can
+/// find where it was written.
+#[derive(Deserialize)]
+#[serde(transparent)]
+struct Text<'a>(#[serde(borrow)] Cow<'a, str>);
+
+/// Line and column, counting from 1 like the RON parser.
+struct Location {
+    line: usize,
+    col: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+impl Location {
+    fn at_offset(source: &str, offset: usize) -> Self {
+        let before = &source[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Self {
+            line: before.matches('\n').count() + 1,
+            col: before[line_start..].chars().count() + 1,
+        }
+    }
+
+    /// Where `text` was written, if it was borrowed from `source`.
+    fn of_text(source: &str, text: &Text) -> Option<Self> {
+        let text = match &text.0 {
+            Cow::Borrowed(text) => text,
+            Cow::Owned(_) => return None,
+        };
+        let offset = (text.as_ptr() as usize).checked_sub(source.as_ptr() as usize)?;
+        if offset > source.len() {
+            return None;
+        }
+        // Point at the opening quote rather than the first character inside it
+        Some(Self::at_offset(source, offset.saturating_sub(1)))
+    }
+}
+
+fn parse(source: &str) -> Result<SceneDescription<'_>, (Location, ron::Error)> {
+    let located = |e: ron::Error| {
+        let location = Location {
+            line: e.position.line,
+            col: e.position.col,
+        };
+        (location, e)
+    };
+    let mut deserializer = ron::de::Deserializer::from_str(source).map_err(located)?;
+    SceneDescription::deserialize(&mut deserializer)
+        .and_then(|description| deserializer.end().map(|_| description))
+        .map_err(|e| {
+            // Errors raised by serde itself (unknown fields, missing fields, bad values) come
+            // without a position, so use wherever the parser stopped.
+            if e.position.line == 0 {
+                let offset = source.len() - deserializer.remainder().len();
+                (Location::at_offset(source, offset), e)
+            } else {
+                located(e)
+            }
+        })
+}
+
+struct Builder<'a> {
+    path: &'a Path,
+    base_dir: &'a Path,
+    source: &'a str,
+    scene: Scene,
+    materials: HashMap<&'a str, u32>,
+    default_material: Option<u32>,
+    imported_camera: Option<CameraDescription>,
+    dependencies: Vec<PathBuf>,
+}
+
+impl<'a> Builder<'a> {
+    fn add_material(&mut self, name: &'a Text, material: &MaterialDescription) -> Result<()> {
+        let converted = match material {
+            MaterialDescription::Lambertian { albedo } => Material::lambertian(*albedo),
+            MaterialDescription::Textured { texture } => {
+                let texture_path = self.base_dir.join(texture.0.as_ref());
+                self.dependencies.push(texture_path.clone());
+                let loaded =
+                    Texture::load(&texture_path).map_err(|e| e.context(self.location(texture)))?;
+                Material::textured(self.scene.add_texture(loaded))
+            }
+            MaterialDescription::Metal { albedo, roughness } => {
+                Material::metal(*albedo, *roughness)
+            }
+            MaterialDescription::Dielectric { ior } => Material::dielectric(*ior),
+            MaterialDescription::Emissive { emission } => Material::emissive(*emission),
+        };
+
+        let id = self.scene.add_material(converted);
+        if self.materials.insert(&name.0, id).is_some() {
+            return Err(anyhow!(
+                "{}: material {:?} is defined twice",
+                self.location(name),
+                name.0
+            ));
+        }
+        Ok(())
+    }
+
+    fn add_object(&mut self, object: &ObjectDescription) -> Result<()> {
+        let material_id = match &object.material {
+            Some(name) => Some(*self.materials.get(name.0.as_ref()).ok_or_else(|| {
+                anyhow!("{}: unknown material {:?}", self.location(name), name.0)
+            })?),
+            None => None,
+        };
+        let transform = placement(object.translate, object.rotate, object.scale);
+        self.add_shape(&object.shape, transform, material_id)
+    }
+
+    fn add_light(&mut self, light: &LightDescription) -> Result<()> {
+        let material_id = self.scene.add_material(Material::emissive(light.emission));
+        let transform = placement(light.translate, light.rotate, light.scale);
+        self.add_shape(&light.shape, transform, Some(material_id))
+    }
+
+    fn add_shape(
+        &mut self,
+        shape: &Shape,
+        transform: Matrix4<f32>,
+        material_id: Option<u32>,
+    ) -> Result<()> {
+        let ty = match shape {
+            Shape::Sphere => GeomType::SPHERE,
+            Shape::Box => GeomType::BOX,
+            Shape::Mesh(path) => {
+                let added = self
+                    .load_mesh(path, transform)
+                    .map_err(|e| e.context(self.location(path)))?;
+                if let Some(material_id) = material_id {
+                    for index in added {
+                        self.scene.geometry[index].material_id = material_id;
+                    }
+                }
+                return Ok(());
+            }
+        };
+
+        let material_id = match material_id.or(self.default_material) {
+            Some(id) => id,
+            None => {
+                let id = self.scene.add_material(Material::default());
+                self.default_material = Some(id);
+                id
+            }
+        };
+        self.scene
+            .add_geometry(Geometry::new(ty, transform, material_id));
+        Ok(())
+    }
+
+    // Returns the indices of the geometry that was added
+    fn load_mesh(&mut self, path: &Text, transform: Matrix4<f32>) -> Result<Vec<usize>> {
+        let mesh_path = self.base_dir.join(path.0.as_ref());
+        self.dependencies.push(mesh_path.clone());
+        let extension = mesh_path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+        match extension.as_deref() {
+            Some("obj") => import::obj::load(&mut self.scene, &mesh_path, transform),
+            Some("gltf") | Some("glb") => {
+                let loaded = import::gltf::load(&mut self.scene, &mesh_path, transform)?;
+                if self.imported_camera.is_none() {
+                    self.imported_camera = loaded.camera.map(CameraDescription::from);
+                }
+                Ok(loaded.geometry)
+            }
+            _ => Err(anyhow!(
+                "Unsupported mesh format {}, expected .obj, .gltf or .glb",
+                mesh_path.display()
+            )),
+        }
+    }
+
+    /// `path:line:column` of `text`, or just the path if it can't be found.
+    fn location(&self, text: &Text) -> String {
+        match Location::of_text(self.source, text) {
+            Some(location) => format!("{}:{}", self.path.display(), location),
+            None => self.path.display().to_string(),
+        }
+    }
+}
+
+/// Scales, then rotates around x, y and z in turn, then translates.
+fn placement(translate: [f32; 3], rotate: [f32; 3], scale: Scale) -> Matrix4<f32> {
+    let [x, y, z] = rotate;
+    let [sx, sy, sz] = scale.0;
+    Matrix4::from_translation(translate.into())
+        * Matrix4::from_angle_z(Deg(z))
+        * Matrix4::from_angle_y(Deg(y))
+        * Matrix4::from_angle_x(Deg(x))
+        * Matrix4::from_nonuniform_scale(sx, sy, sz)
+}
+
+/// Lets optional fields be written without RON's `Some(...)`.
+fn some<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// Reads a map keeping the order it was written in, so material ids follow the file and
+/// repeated names can be reported instead of silently replacing each other.
+fn ordered_map<'de, D, K, V>(deserializer: D) -> Result<Vec<(K, V)>, D::Error>
+where
+    D: Deserializer<'de>,
+    K: Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    struct OrderedMap<K, V>(std::marker::PhantomData<(K, V)>);
+
+    impl<'de, K: Deserialize<'de>, V: Deserialize<'de>> Visitor<'de> for OrderedMap<K, V> {
+        type Value = Vec<(K, V)>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a map")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0));
+            while let Some(entry) = map.next_entry()? {
+                entries.push(entry);
+            }
+            Ok(entries)
+        }
+    }
+
+    deserializer.deserialize_map(OrderedMap(std::marker::PhantomData))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_str(source: &str) -> Result<SceneFile> {
+        SceneFile::from_source(Path::new("test.ron"), source)
+    }
+
+    fn error(source: &str) -> String {
+        match from_str(source) {
+            Ok(_) => panic!("{:?} should not load", source),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn every_section() {
+        let file = from_str(
+            r#"
+Scene(
+    camera: (position: (0, 1, -4), target: (0, 0, 3), up: (0, 1, 0), fovy: 30),
+    settings: (width: 640, samples_per_pixel: 16),
+    materials: {
+        "ground": Lambertian(albedo: (0.5, 0.5, 0.5)),
+        "gold": Metal(albedo: (0.8, 0.6, 0.2)),
+        "glass": Dielectric(ior: 1.5),
+    },
+    objects: [
+        (shape: Sphere, material: "glass", translate: (0, 0, 3)),
+        (shape: Box, material: "ground", rotate: (0, 45, 0), scale: (2, 0.5, 1)),
+        (shape: Sphere),
+    ],
+    lights: [
+        (shape: Sphere, emission: (4, 4, 4), translate: (0, 5, 3)),
+    ],
+)
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(file.camera.position, [0.0, 1.0, -4.0]);
+        assert_eq!(file.camera.fovy, 30.0);
+        assert_eq!(file.settings.width, Some(640));
+        assert_eq!(file.settings.height, None);
+        assert_eq!(file.settings.samples_per_pixel, Some(16));
+
+        // Materials in file order, then the default one and the light's
+        let scene = &file.scene;
+        assert_eq!(scene.materials.len(), 5);
+        assert_eq!(scene.materials[1].roughness, 0.0);
+        assert_eq!(scene.materials[2].ior, 1.5);
+        assert_eq!(scene.materials[4].emission, [4.0; 3]);
+        let materials: Vec<u32> = scene.geometry.iter().map(|g| g.material_id).collect();
+        assert_eq!(materials, vec![2, 0, 3, 4]);
+        assert_eq!(scene.geometry[1].ty, GeomType::BOX);
+        assert_eq!(scene.geometry[3].transf[3], [0.0, 5.0, 3.0, 1.0]);
+    }
+
+    #[test]
+    fn everything_is_optional() {
+        let file = from_str("Scene()").unwrap();
+        assert_eq!(file.camera.fovy, CameraDescription::default().fovy);
+        assert!(file.scene.geometry.is_empty());
+    }
+
+    #[test]
+    fn syntax_error_location() {
+        let source =
+            "Scene(\n    materials: {\n        \"a\" Lambertian(albedo: (1, 1, 1)),\n    },\n)";
+        assert_eq!(error(source), "test.ron:3:13: Expected colon");
+    }
+
+    #[test]
+    fn unknown_field_location() {
+        let source = "Scene(\n    objects: [\n        (shape: Sphere, colour: \"red\"),\n    ],\n)";
+        // serde doesn't say where, so it's wherever the parser stopped: just past the name
+        assert!(error(source).starts_with("test.ron:3:31: unknown field `colour`"));
+        let source = "Scene(\n    camera: (fovy: 30, zoom: 2),\n)";
+        assert!(error(source).starts_with("test.ron:2:28: unknown field `zoom`"));
+    }
+
+    #[test]
+    fn zero_scale() {
+        let source = "Scene(objects: [(shape: Box, scale: (1, 0, 1))])";
+        assert_eq!(error(source), "test.ron:1:46: scale can't be zero");
+    }
+
+    #[test]
+    fn duplicate_material() {
+        let source = r#"Scene(
+    materials: {
+        "a": Lambertian(albedo: (1, 1, 1)),
+        "a": Dielectric(ior: 1.5),
+    },
+)"#;
+        assert_eq!(
+            error(source),
+            "test.ron:4:9: material \"a\" is defined twice"
+        );
+    }
+
+    #[test]
+    fn unknown_material() {
+        let source = r#"Scene(
+    materials: {
+        "a": Lambertian(albedo: (1, 1, 1)),
+    },
+    objects: [(shape: Sphere, material: "b")],
+)"#;
+        assert_eq!(error(source), "test.ron:5:41: unknown material \"b\"");
+    }
+
+    #[test]
+    fn loads_spheres() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/scenes/spheres.ron");
+        let file = SceneFile::load(Path::new(path)).unwrap();
+        assert_eq!(file.scene.geometry.len(), 6);
+        assert_eq!(file.scene.materials.len(), 5);
+        assert_eq!(file.camera.fovy, 30.0);
+        assert_eq!(file.settings.samples_per_pixel, Some(64));
+        assert_eq!(file.settings.width, None);
+    }
+}
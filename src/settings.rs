@@ -0,0 +1,56 @@
+use serde::Deserialize;
+
+use crate::bvh::BvhBuildOptions;
+
+/// How an image gets rendered, independent of where it ends up.
+#[derive(Debug, Clone, Copy)]
+pub struct RenderSettings {
+    pub samples_per_pixel: u32,
+    pub max_bounces: u32,
+    /// Picks the random numbers, the same seed renders the same image
+    pub seed: u32,
+    /// What the random numbers look like, see `Sampler`
+    pub sampler: Sampler,
+    pub bvh: BvhBuildOptions,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            samples_per_pixel: 1,
+            max_bounces: 8,
+            seed: 0,
+            sampler: Sampler::Random,
+            bvh: BvhBuildOptions::default(),
+        }
+    }
+}
+
+/// Where the numbers behind each sample's random decisions come from. The camera and every
+/// bounce draw theirs from the same sampler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Sampler {
+    /// Independent random numbers
+    Random,
+    /// Owen-scrambled Sobol, a different scramble for every pixel
+    Sobol,
+    /// Halton, shifted by a different amount for every pixel
+    Halton,
+    /// The same scrambled Sobol points in every pixel, shifted by a blue noise mask. What error
+    /// is left looks like fine, even grain instead of blotches.
+    BlueNoise,
+}
+
+impl std::str::FromStr for Sampler {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "random" => Ok(Sampler::Random),
+            "sobol" => Ok(Sampler::Sobol),
+            "halton" => Ok(Sampler::Halton),
+            "blue-noise" => Ok(Sampler::BlueNoise),
+            _ => Err(format!("Unknown sampler: {}", name)),
+        }
+    }
+}
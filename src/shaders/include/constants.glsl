@@ -0,0 +1,21 @@
+#ifndef CONSTANTS_GLSL
+#define CONSTANTS_GLSL
+
+// GeomType in src/data_types.rs
+const uint SPHERE = 1;
+const uint BOX = 2;
+const uint TRIANGLE = 4;
+
+// MaterialType in src/data_types.rs
+const uint LAMBERTIAN = 1;
+const uint METAL = 2;
+const uint DIELECTRIC = 4;
+const uint EMISSIVE = 8;
+
+const float PI = 3.14159265359;
+// Keeps new rays from hitting the surface they start on
+const float EPSILON = 1e-4;
+// Distance reported for rays that hit nothing
+const float NO_HIT = 1e30;
+
+#endif
@@ -0,0 +1,61 @@
+#ifndef MATH_GLSL
+#define MATH_GLSL
+
+#include "constants.glsl"
+
+// PCG-RXS-M-XS, one step of the LCG followed by its output permutation
+uint pcg_hash(uint v) {
+    uint state = v * 747796405u + 2891336453u;
+    uint word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// Random state for one pixel's sample. Each pass that needs random numbers picks its own
+// `stream`, so the passes don't reuse each other's numbers, and the same `seed` always gives
+// the same image.
+uint rng_init(uint pixel, uint frame, uint seed, uint stream) {
+    return pcg_hash(pixel + pcg_hash(frame + pcg_hash(seed + pcg_hash(stream))));
+}
+
+// Uniform float in [0, 1), advances the state. Only the top 24 bits are used, any more
+// could round up to 1.0.
+float rand(inout uint state) {
+    state = state * 747796405u + 2891336453u;
+    uint word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    word = (word >> 22u) ^ word;
+    return float(word >> 8u) / 16777216.0;
+}
+
+// The warps below turn uniform samples in [0, 1) into the distribution they're named after
+vec3 cosine_sample_hemisphere(vec3 normal, vec2 u) {
+    float r = sqrt(u.x);
+    float phi = 2.0 * PI * u.y;
+
+    vec3 tangent = normalize(abs(normal.x) > 0.9
+        ? cross(normal, vec3(0.0, 1.0, 0.0))
+        : cross(normal, vec3(1.0, 0.0, 0.0)));
+    vec3 bitangent = cross(normal, tangent);
+
+    return normalize(
+        r * cos(phi) * tangent +
+        r * sin(phi) * bitangent +
+        sqrt(max(0.0, 1.0 - u.x)) * normal
+    );
+}
+
+// Uniform over the unit disk
+vec2 sample_unit_disk(vec2 u) {
+    float r = sqrt(u.x);
+    float phi = 2.0 * PI * u.y;
+    return vec2(r * cos(phi), r * sin(phi));
+}
+
+// Uniform over the unit ball
+vec3 sample_unit_sphere(vec3 u) {
+    float z = 1.0 - 2.0 * u.x;
+    float r = sqrt(max(0.0, 1.0 - z * z));
+    float phi = 2.0 * PI * u.y;
+    return vec3(r * cos(phi), r * sin(phi), z) * pow(u.z, 1.0 / 3.0);
+}
+
+#endif
@@ -0,0 +1,171 @@
+// Sample generators shared by every pass that makes random decisions. src/sampling.rs
+// mirrors them, and its tests check that the tables match and the points stay stratified.
+//
+// Shaders including this define BLUE_NOISE_BINDING first, the binding of the blue noise
+// mask in their set.
+#ifndef SAMPLING_GLSL
+#define SAMPLING_GLSL
+
+#include "math.glsl"
+
+// Sampler in settings.rs, in the same order
+#define SAMPLER_RANDOM 0
+#define SAMPLER_SOBOL 1
+#define SAMPLER_HALTON 2
+#define SAMPLER_BLUE_NOISE 3
+
+// Dimensions of each sample: the camera takes the first four (pixel jitter, then the lens)
+// and every bounce the next four (picking a lobe, then the direction)
+#define CAMERA_DIMENSIONS 4
+#define BOUNCE_DIMENSIONS 4
+
+#define BLUE_NOISE_SIZE 64u
+
+layout (std430, set = 0, binding = BLUE_NOISE_BINDING) readonly buffer BlueNoise {
+    float data[];
+} blue_noise;
+
+// Generator matrices of the first four Sobol dimensions back to back, one column per index
+// bit
+const uint SOBOL_DIRECTIONS[128] = uint[128](
+    0x80000000u, 0x40000000u, 0x20000000u, 0x10000000u, 0x08000000u, 0x04000000u, 0x02000000u, 0x01000000u,
+    0x00800000u, 0x00400000u, 0x00200000u, 0x00100000u, 0x00080000u, 0x00040000u, 0x00020000u, 0x00010000u,
+    0x00008000u, 0x00004000u, 0x00002000u, 0x00001000u, 0x00000800u, 0x00000400u, 0x00000200u, 0x00000100u,
+    0x00000080u, 0x00000040u, 0x00000020u, 0x00000010u, 0x00000008u, 0x00000004u, 0x00000002u, 0x00000001u,
+    0x80000000u, 0xc0000000u, 0xa0000000u, 0xf0000000u, 0x88000000u, 0xcc000000u, 0xaa000000u, 0xff000000u,
+    0x80800000u, 0xc0c00000u, 0xa0a00000u, 0xf0f00000u, 0x88880000u, 0xcccc0000u, 0xaaaa0000u, 0xffff0000u,
+    0x80008000u, 0xc000c000u, 0xa000a000u, 0xf000f000u, 0x88008800u, 0xcc00cc00u, 0xaa00aa00u, 0xff00ff00u,
+    0x80808080u, 0xc0c0c0c0u, 0xa0a0a0a0u, 0xf0f0f0f0u, 0x88888888u, 0xccccccccu, 0xaaaaaaaau, 0xffffffffu,
+    0x80000000u, 0xc0000000u, 0x60000000u, 0x90000000u, 0xe8000000u, 0x5c000000u, 0x8e000000u, 0xc5000000u,
+    0x68800000u, 0x9cc00000u, 0xee600000u, 0x55900000u, 0x80680000u, 0xc09c0000u, 0x60ee0000u, 0x90550000u,
+    0xe8808000u, 0x5cc0c000u, 0x8e606000u, 0xc5909000u, 0x6868e800u, 0x9c9c5c00u, 0xeeee8e00u, 0x5555c500u,
+    0x8000e880u, 0xc0005cc0u, 0x60008e60u, 0x9000c590u, 0xe8006868u, 0x5c009c9cu, 0x8e00eeeeu, 0xc5005555u,
+    0x80000000u, 0xc0000000u, 0x20000000u, 0x50000000u, 0xf8000000u, 0x74000000u, 0xa2000000u, 0x93000000u,
+    0xd8800000u, 0x25400000u, 0x59e00000u, 0xe6d00000u, 0x78080000u, 0xb40c0000u, 0x82020000u, 0xc3050000u,
+    0x208f8000u, 0x51474000u, 0xfbea2000u, 0x75d93000u, 0xa0858800u, 0x914e5400u, 0xdbe79e00u, 0x25db6d00u,
+    0x58800080u, 0xe54000c0u, 0x79e00020u, 0xb6d00050u, 0x800800f8u, 0xc00c0074u, 0x200200a2u, 0x50050093u
+);
+
+const uint HALTON_PRIMES[64] = uint[64](
+    2u, 3u, 5u, 7u, 11u, 13u, 17u, 19u, 23u, 29u, 31u, 37u, 41u, 43u, 47u, 53u,
+    59u, 61u, 67u, 71u, 73u, 79u, 83u, 89u, 97u, 101u, 103u, 107u, 109u, 113u, 127u, 131u,
+    137u, 139u, 149u, 151u, 157u, 163u, 167u, 173u, 179u, 181u, 191u, 193u, 197u, 199u, 211u, 223u,
+    227u, 229u, 233u, 239u, 241u, 251u, 257u, 263u, 269u, 271u, 277u, 281u, 283u, 293u, 307u, 311u
+);
+
+// Where one pixel's sample is in its sequence
+struct Sampler {
+    uint type;
+    uvec2 pixel;
+    // Scrambles the sequence differently in every pixel
+    uint pixel_seed;
+    uint seed;
+    // Sample number, the same for every pixel
+    uint index;
+    uint dimension;
+    // Only used by SAMPLER_RANDOM
+    uint rng;
+};
+
+uint hash_combine(uint seed, uint v) {
+    return seed ^ (pcg_hash(v) + (seed << 6) + (seed >> 2));
+}
+
+// The top 24 bits as a float in [0, 1), any more could round up to 1.0
+float to_unit(uint x) {
+    return float(x >> 8u) / 16777216.0;
+}
+
+uint sobol(uint index, uint dimension) {
+    uint x = 0u;
+    for (uint bit = 0u; index != 0u; bit++, index >>= 1u) {
+        if ((index & 1u) != 0u) {
+            x ^= SOBOL_DIRECTIONS[dimension * 32u + bit];
+        }
+    }
+    return x;
+}
+
+uint laine_karras_permutation(uint x, uint seed) {
+    x += seed;
+    x ^= x * 0x6c50b47cu;
+    x ^= x * 0xb82f1e52u;
+    x ^= x * 0xc7afe638u;
+    x ^= x * 0x8d22f6e6u;
+    return x;
+}
+
+// Owen scrambling: every bit gets flipped depending on the bits above it
+uint nested_uniform_scramble(uint x, uint seed) {
+    return bitfieldReverse(laine_karras_permutation(bitfieldReverse(x), seed));
+}
+
+// Owen-scrambled Sobol, shuffled and padded in groups of four dimensions (Burley, "Practical
+// Hash-based Owen Scrambling", 2020)
+float sobol_owen(uint index, uint dimension, uint seed) {
+    uint group_seed = hash_combine(seed, dimension / 4u);
+    uint shuffled = nested_uniform_scramble(index, group_seed);
+    uint x = sobol(shuffled, dimension % 4u);
+    return to_unit(nested_uniform_scramble(x, hash_combine(group_seed, dimension % 4u)));
+}
+
+float radical_inverse(uint base, uint index) {
+    float inverse_base = 1.0 / float(base);
+    float digit_value = inverse_base;
+    float x = 0.0;
+    while (index > 0u) {
+        x += float(index % base) * digit_value;
+        index /= base;
+        digit_value *= inverse_base;
+    }
+    return x;
+}
+
+// Halton, shifted around the unit interval by a per pixel hash
+float halton(uint index, uint dimension, uint seed) {
+    uint base = HALTON_PRIMES[dimension % 64u];
+    float shift = to_unit(hash_combine(seed, dimension));
+    return fract(radical_inverse(base, index) + shift);
+}
+
+// Every dimension reads the mask at its own offset, so they don't shift together
+float blue_noise_shift(uvec2 pixel, uint dimension, uint seed) {
+    uint offset = pcg_hash(hash_combine(seed, dimension));
+    uvec2 p = (pixel + uvec2(offset, offset >> 16u)) % uvec2(BLUE_NOISE_SIZE);
+    return blue_noise.data[p.y * BLUE_NOISE_SIZE + p.x];
+}
+
+// Starts the sample `index` of pixel `pixel_index` at `dimension`. Each pass starts where its
+// dimensions begin, so the passes don't reuse each other's numbers.
+Sampler sampler_init(uint type, uint pixel_index, uint width, uint index, uint seed, uint dimension) {
+    return Sampler(
+        type,
+        uvec2(pixel_index % width, pixel_index / width),
+        hash_combine(seed, pixel_index),
+        seed,
+        index,
+        dimension,
+        rng_init(pixel_index, index, seed, dimension)
+    );
+}
+
+// The next dimension of the sample, in [0, 1)
+float next_sample(inout Sampler s) {
+    uint dimension = s.dimension++;
+    if (s.type == SAMPLER_SOBOL) {
+        return sobol_owen(s.index, dimension, s.pixel_seed);
+    } else if (s.type == SAMPLER_HALTON) {
+        return halton(s.index, dimension, s.pixel_seed);
+    } else if (s.type == SAMPLER_BLUE_NOISE) {
+        float x = sobol_owen(s.index, dimension, s.seed);
+        return fract(x + blue_noise_shift(s.pixel, dimension, s.seed));
+    }
+    return rand(s.rng);
+}
+
+vec2 next_sample_2d(inout Sampler s) {
+    float x = next_sample(s);
+    return vec2(x, next_sample(s));
+}
+
+#endif
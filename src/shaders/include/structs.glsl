@@ -0,0 +1,71 @@
+// Structs shared between the passes. They have to match their counterparts in
+// src/data_types.rs, which use the std430 layout. The build checks that they do.
+#ifndef STRUCTS_GLSL
+#define STRUCTS_GLSL
+
+struct Ray {
+    vec3 origin;
+    vec3 direction;
+};
+
+struct PathSegment {
+    Ray ray;
+    vec3 throughput;
+    uint pixel_index;
+    vec3 radiance;
+    int remaining_bounces;
+};
+
+struct Intersection {
+    vec3 surface_normal;
+    float t;
+    vec2 uv;
+    uint material_id;
+};
+
+struct Geometry {
+    mat4 transf;
+    mat4 inverse;
+    mat4 transp_inv;
+    uint type;
+    uint material_id;
+    uint mesh_id;
+};
+
+struct MeshVertex {
+    vec3 position;
+    vec3 normal;
+    vec2 uv;
+};
+
+struct MeshInfo {
+    uint first_index;
+    uint index_count;
+    uint base_vertex;
+    uint bvh_root;
+};
+
+struct BvhNode {
+    vec3 min;
+    uint offset;
+    vec3 max;
+    uint count;
+};
+
+struct Material {
+    vec3 albedo;
+    uint type;
+    vec3 emission;
+    float roughness;
+    float ior;
+    // Index into the texture table, negative when the albedo is a plain color
+    int texture_id;
+};
+
+struct TextureInfo {
+    uint offset;
+    uint width;
+    uint height;
+};
+
+#endif
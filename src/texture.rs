@@ -0,0 +1,32 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+/// An sRGB image, stored the way the shade pass reads it: one RGBA8 texel per u32, rows
+/// from the top down.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Texture {
+    pub width: u32,
+    pub height: u32,
+    pub texels: Vec<u32>,
+}
+
+impl Texture {
+    pub fn load(path: &Path) -> Result<Self> {
+        let image = image::open(path)
+            .with_context(|| format!("Unable to load texture {}", path.display()))?;
+        Ok(Self::from_image(&image.to_rgba8()))
+    }
+
+    pub fn from_image(image: &image::RgbaImage) -> Self {
+        let texels = image
+            .pixels()
+            .map(|pixel| u32::from_le_bytes(pixel.0))
+            .collect();
+        Self {
+            width: image.width(),
+            height: image.height(),
+            texels,
+        }
+    }
+}
@@ -1,15 +1,20 @@
+use std::path::Path;
+use std::time::Instant;
+
+use cgmath::{InnerSpace, Vector3};
+
 use winit::{event::*, window::Window};
 
-mod camera;
-mod data_types;
-mod gpu_buffer;
-mod pathtracer;
-mod scene;
+mod fly_camera;
+#[cfg(feature = "shader-reload")]
+mod shader_reload;
+mod vertex;
+mod watcher;
 
-use camera::Camera;
-use gpu_buffer::{GPUBuffer, GPUBufferDescription};
-use pathtracer::Pathtracer;
-use scene::Scene;
+use oscuras::gpu_buffer::{GPUBuffer, GPUBufferDescription};
+use oscuras::intersect;
+use oscuras::scene_file::{CameraDescription, SceneFile};
+use oscuras::{Camera, Pathtracer, Projection, RenderSettings, Scene};
 
 pub struct Viewer {
     surface: wgpu::Surface,
@@ -19,24 +24,50 @@ pub struct Viewer {
     swap_chain: wgpu::SwapChain,
     size: winit::dpi::PhysicalSize<u32>,
     viewer_pipeline: wgpu::RenderPipeline,
+    viewer_bgl: wgpu::BindGroupLayout,
     viewer_bg: wgpu::BindGroup,
     vertex_buffer: GPUBuffer,
     num_vertices: u32,
     camera: Camera,
+    // Where the camera came from, so reloading the scene only moves it if the file did
+    camera_description: CameraDescription,
+    // Set from the command line, which wins over the scene file's
+    projection: Option<Projection>,
+    controls: fly_camera::FlyCamera,
+    last_update: Instant,
     scene: Scene,
+    scene_watcher: Option<watcher::SceneWatcher>,
+    #[cfg(feature = "shader-reload")]
+    shader_reloader: Option<shader_reload::ShaderReloader>,
     pathtracer: Pathtracer,
 }
 
 impl Viewer {
-    pub async fn new(window: &Window) -> Self {
+    pub async fn new(
+        window: &Window,
+        backend: wgpu::BackendBit,
+        file: SceneFile,
+        scene_path: Option<&Path>,
+        projection: Option<Projection>,
+        settings: RenderSettings,
+    ) -> Self {
         let size = window.inner_size();
 
-        let camera = Camera::new(&size);
-        let scene = Scene::new();
+        // The window may not have gotten the size it asked for
+        let camera_description = file.camera.with_projection(projection);
+        let camera = camera_description.camera(size.width, size.height);
+
+        let scene_watcher = scene_path.and_then(|path| {
+            watcher::SceneWatcher::new(path, &file.dependencies)
+                .map_err(|e| log::warn!("Not watching {} for changes: {}", path.display(), e))
+                .ok()
+        });
+
+        let scene = file.scene;
 
         // The instance is a handle to our GPU
-        // BackendBit::PRIMARY => Vulkan + Metal + DX12+ Browser WebGPU
-        let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
+        // The CLI defaults to BackendBit::PRIMARY => Vulkan + Metal + DX12+ Browser WebGPU
+        let instance = wgpu::Instance::new(backend);
         let surface = unsafe { instance.create_surface(window) };
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
@@ -51,7 +82,7 @@ impl Viewer {
                 &wgpu::DeviceDescriptor {
                     label: Some("device"),
                     features: wgpu::Features::empty(),
-                    limits: wgpu::Limits::default(),
+                    limits: Pathtracer::required_limits(),
                 },
                 None,
             )
@@ -69,14 +100,19 @@ impl Viewer {
         };
         let swap_chain = device.create_swap_chain(&surface, &sc_desc);
 
-        let pathtracer = Pathtracer::new(&device, &camera, &scene);
+        let pathtracer = Pathtracer::new(&device, &camera, &scene, settings);
+
+        #[cfg(feature = "shader-reload")]
+        let shader_reloader = shader_reload::ShaderReloader::new(&device)
+            .map_err(|e| log::warn!("Shaders won't be reloaded: {:#}", e))
+            .ok();
 
         // Set up the vertex buffer for our quad
-        let num_vertices = data_types::VERTICES.len() as u32;
-        let vert_buf_desc = GPUBufferDescription::<data_types::Vertex> {
-            contents: Some(data_types::VERTICES),
+        let num_vertices = vertex::VERTICES.len() as u32;
+        let vert_buf_desc = GPUBufferDescription::<vertex::Vertex> {
+            contents: Some(vertex::VERTICES),
             element_count: num_vertices,
-            element_size: std::mem::size_of::<data_types::Vertex>(),
+            element_size: std::mem::size_of::<vertex::Vertex>(),
             usage: wgpu::BufferUsage::VERTEX,
         };
         let vertex_buffer = GPUBuffer::new(&device, vert_buf_desc);
@@ -112,24 +148,7 @@ impl Viewer {
             ],
         });
 
-        let viewer_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("display_bind_group"),
-            layout: &viewer_bgl,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(
-                        &pathtracer
-                            .texture()
-                            .create_view(&wgpu::TextureViewDescriptor::default()),
-                    ),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&pathtracer.sampler()),
-                },
-            ],
-        });
+        let viewer_bg = display_bind_group(&device, &viewer_bgl, &pathtracer);
 
         let viewer_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: None,
@@ -143,7 +162,7 @@ impl Viewer {
             vertex: wgpu::VertexState {
                 module: &vs_module,
                 entry_point: "main",
-                buffers: &[data_types::Vertex::desc()],
+                buffers: &[vertex::Vertex::desc()],
             },
             fragment: Some(wgpu::FragmentState {
                 module: &fs_module,
@@ -165,25 +184,129 @@ impl Viewer {
             viewer_pipeline,
             vertex_buffer,
             num_vertices,
+            viewer_bgl,
             viewer_bg,
             camera,
+            controls: fly_camera::FlyCamera::new(Vector3::from(file.camera.up)),
+            last_update: Instant::now(),
+            camera_description,
+            projection,
             scene,
+            scene_watcher,
+            #[cfg(feature = "shader-reload")]
+            shader_reloader,
             pathtracer,
         }
     }
 
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        // Minimizing shrinks the window to nothing, which nothing can be allocated for
+        if new_size.width == 0 || new_size.height == 0 {
+            return;
+        }
         self.size = new_size;
         self.sc_desc.width = new_size.width;
         self.sc_desc.height = new_size.height;
         self.swap_chain = self.device.create_swap_chain(&self.surface, &self.sc_desc);
+
+        if new_size.width != self.camera.res_x() || new_size.height != self.camera.res_y() {
+            self.camera = self.camera.with_resolution(new_size.width, new_size.height);
+            self.pathtracer
+                .resize(&self.device, &self.queue, &self.camera);
+            self.viewer_bg = display_bind_group(&self.device, &self.viewer_bgl, &self.pathtracer);
+        }
     }
 
     pub fn input(&mut self, event: &WindowEvent) -> bool {
-        false
+        if let WindowEvent::MouseInput {
+            state: ElementState::Pressed,
+            button: MouseButton::Right,
+            ..
+        } = event
+        {
+            if let Some(cursor) = self.controls.cursor() {
+                self.focus_at(cursor.x as f32, cursor.y as f32);
+            }
+            return true;
+        }
+        self.controls.input(event)
     }
 
-    pub fn update(&mut self) {}
+    /// Moves the focus plane onto whatever is under pixel (`x`, `y`).
+    fn focus_at(&mut self, x: f32, y: f32) {
+        // Only the perspective camera has a lens, the orthographic one sizes its view by the
+        // focus distance instead
+        if self.camera.projection() != Projection::Perspective {
+            log::info!("Only perspective cameras can focus");
+            return;
+        }
+        let (origin, direction) = match self.camera.primary_ray(x, y) {
+            Some(ray) => ray,
+            None => return,
+        };
+        match intersect::closest_hit(&self.scene, self.pathtracer.bvh(), origin, direction) {
+            Some(hit) => {
+                // The focus distance is measured along the view direction, not the ray
+                let distance = hit.t * direction.dot(self.camera.direction());
+                log::info!("Focused at {:.3}", distance);
+                self.camera.set_focus_distance(distance);
+                self.pathtracer.update_camera(&self.queue, &self.camera);
+            }
+            None => log::info!("Nothing under the cursor to focus on"),
+        }
+    }
+
+    pub fn update(&mut self) {
+        let scene_changed = matches!(&self.scene_watcher, Some(watcher) if watcher.changed());
+        if scene_changed {
+            self.reload_scene();
+        }
+        #[cfg(feature = "shader-reload")]
+        if let Some(reloader) = &mut self.shader_reloader {
+            reloader.update(&self.device, &mut self.pathtracer);
+        }
+        self.pathtracer.apply_edits(&self.queue, &mut self.scene);
+
+        let now = Instant::now();
+        if let Some(camera) = self.controls.update(&self.camera, now - self.last_update) {
+            self.camera = camera;
+            self.pathtracer.update_camera(&self.queue, &self.camera);
+        }
+        self.last_update = now;
+    }
+
+    /// Loads the scene file again and swaps it in. A broken file keeps the scene that is
+    /// already showing, so it can be fixed without restarting.
+    fn reload_scene(&mut self) {
+        let watcher = match &mut self.scene_watcher {
+            Some(watcher) => watcher,
+            None => return,
+        };
+        let file = match SceneFile::load(watcher.path()) {
+            Ok(file) => file,
+            Err(e) => {
+                log::error!("Keeping the previous scene: {:#}", e);
+                return;
+            }
+        };
+        log::info!("Reloaded {}", watcher.path().display());
+        watcher.watch(&file.dependencies);
+
+        // Only moving things around just needs the BVH refit, which `update` does next
+        if !self.scene.match_transforms(&file.scene) {
+            self.pathtracer
+                .set_scene(&self.device, &self.queue, &file.scene);
+            self.scene = file.scene;
+        }
+        let camera_description = file.camera.with_projection(self.projection);
+        if camera_description != self.camera_description {
+            self.camera = camera_description.camera(self.camera.res_x(), self.camera.res_y());
+            self.controls
+                .set_world_up(Vector3::from(camera_description.up));
+            self.camera_description = camera_description;
+            self.pathtracer.update_camera(&self.queue, &self.camera);
+        }
+    }
 
     pub fn render(&mut self) -> Result<(), wgpu::SwapChainError> {
         let frame = self.swap_chain.get_current_frame()?.output;
@@ -194,7 +317,7 @@ impl Viewer {
                 label: Some("Render Encoder"),
             });
 
-        self.pathtracer.run(&mut encoder);
+        self.pathtracer.run(&self.queue, &mut encoder);
 
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: None,
@@ -230,3 +353,29 @@ impl Viewer {
         self.size
     }
 }
+
+/// Binds the pathtracer's image for the quad to sample, made again whenever it's resized.
+fn display_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    pathtracer: &Pathtracer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("display_bind_group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(
+                    &pathtracer
+                        .texture()
+                        .create_view(&wgpu::TextureViewDescriptor::default()),
+                ),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(pathtracer.sampler()),
+            },
+        ],
+    })
+}
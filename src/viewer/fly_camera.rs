@@ -0,0 +1,182 @@
+use std::time::Duration;
+
+use cgmath::{InnerSpace, Matrix3, Rad, Vector3};
+use winit::dpi::PhysicalPosition;
+use winit::event::*;
+
+use oscuras::{Camera, Projection};
+
+// World units per second
+const MOVE_SPEED: f32 = 2.0;
+// Radians per pixel the cursor is dragged
+const LOOK_SPEED: f32 = 0.004;
+// Degrees per scroll wheel line
+const ZOOM_SPEED: f32 = 2.0;
+// Keeps the tangent of the field of view finite
+const MIN_FOVY: f32 = 1.0;
+const MAX_FOVY: f32 = 80.0;
+// A fisheye has no tangent to keep finite and can see all the way around
+const MAX_FISHEYE_FOVY: f32 = 180.0;
+// How close to straight up or down the camera may look, so `up` never lines up with it
+const MAX_PITCH_COS: f32 = 0.99;
+
+/// First person controls for the viewer camera: WASD moves, Q and E go down and up, dragging
+/// with the left mouse button looks around and the scroll wheel zooms.
+///
+/// Only the input is kept here. Each update starts from the camera as it is, so a camera
+/// replaced by a scene reload is picked up without telling the controls.
+pub struct FlyCamera {
+    // The scene's up, which movement and looking stay level with
+    world_up: Vector3<f32>,
+    forward: bool,
+    backward: bool,
+    left: bool,
+    right: bool,
+    up: bool,
+    down: bool,
+    looking: bool,
+    cursor: Option<PhysicalPosition<f64>>,
+    // Accumulated since the last update, in pixels and scroll lines
+    look_delta: (f32, f32),
+    zoom_delta: f32,
+}
+
+impl FlyCamera {
+    pub fn new(world_up: Vector3<f32>) -> Self {
+        Self {
+            world_up: world_up.normalize(),
+            forward: false,
+            backward: false,
+            left: false,
+            right: false,
+            up: false,
+            down: false,
+            looking: false,
+            cursor: None,
+            look_delta: (0.0, 0.0),
+            zoom_delta: 0.0,
+        }
+    }
+
+    pub fn set_world_up(&mut self, world_up: Vector3<f32>) {
+        self.world_up = world_up.normalize();
+    }
+
+    /// Records the event if it's one of the controls, returns whether it was.
+    pub fn input(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } => {
+                let pressed = *state == ElementState::Pressed;
+                match key {
+                    VirtualKeyCode::W => self.forward = pressed,
+                    VirtualKeyCode::S => self.backward = pressed,
+                    VirtualKeyCode::A => self.left = pressed,
+                    VirtualKeyCode::D => self.right = pressed,
+                    VirtualKeyCode::E => self.up = pressed,
+                    VirtualKeyCode::Q => self.down = pressed,
+                    _ => return false,
+                }
+                true
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => {
+                self.looking = *state == ElementState::Pressed;
+                true
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                if let (true, Some(last)) = (self.looking, self.cursor) {
+                    self.look_delta.0 += (position.x - last.x) as f32;
+                    self.look_delta.1 += (position.y - last.y) as f32;
+                }
+                self.cursor = Some(*position);
+                self.looking
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+                false
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.zoom_delta += match delta {
+                    MouseScrollDelta::LineDelta(_, lines) => *lines,
+                    // Roughly what one line scrolls on platforms that report pixels
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 20.0,
+                };
+                true
+            }
+            // Keys held while the window lost focus would never see their release
+            WindowEvent::Focused(false) => {
+                *self = Self::new(self.world_up);
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// Applies the input since the last call to `camera`, returning the moved camera if
+    /// anything changed.
+    pub fn update(&mut self, camera: &Camera, elapsed: Duration) -> Option<Camera> {
+        let mut position = camera.position();
+        let mut direction = camera.direction();
+        let mut fovy = camera.fovy();
+        let mut moved = false;
+
+        let (dx, dy) = std::mem::take(&mut self.look_delta);
+        if dx != 0.0 || dy != 0.0 {
+            // Yaw around the world up, then pitch sideways, turning the way the cursor
+            // moves like a first person game
+            let yaw = Matrix3::from_axis_angle(self.world_up, Rad(-dx * LOOK_SPEED));
+            direction = yaw * direction;
+            let side = self.world_up.cross(direction).normalize();
+            let pitch = Matrix3::from_axis_angle(side, Rad(dy * LOOK_SPEED));
+            let pitched = pitch * direction;
+            if pitched.dot(self.world_up).abs() < MAX_PITCH_COS {
+                direction = pitched;
+            }
+            moved = true;
+        }
+
+        let zoom = std::mem::take(&mut self.zoom_delta);
+        if zoom != 0.0 {
+            let max_fovy = match camera.projection() {
+                Projection::Fisheye => MAX_FISHEYE_FOVY,
+                _ => MAX_FOVY,
+            };
+            fovy = (fovy - zoom * ZOOM_SPEED).clamp(MIN_FOVY, max_fovy);
+            moved = true;
+        }
+
+        // Walking stays level, only Q and E change the height
+        let ahead = (direction - self.world_up * direction.dot(self.world_up)).normalize();
+        // The camera's own `right` points the other way, the shaders walk pixels against it
+        let right = ahead.cross(self.world_up);
+        let axis = |positive: bool, negative: bool| positive as i32 as f32 - negative as i32 as f32;
+        let step = ahead * axis(self.forward, self.backward)
+            + right * axis(self.right, self.left)
+            + self.world_up * axis(self.up, self.down);
+        if step.magnitude2() > 0.0 {
+            position += step.normalize() * MOVE_SPEED * elapsed.as_secs_f32();
+            moved = true;
+        }
+
+        if !moved {
+            return None;
+        }
+        Some(camera.with_view(position, position + direction, self.world_up, fovy))
+    }
+
+    /// Where the cursor is over the window, if it is.
+    pub fn cursor(&self) -> Option<PhysicalPosition<f64>> {
+        self.cursor
+    }
+}
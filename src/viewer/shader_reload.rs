@@ -0,0 +1,160 @@
+//! Development mode that recompiles the compute shaders whenever their sources change, so the
+//! shading code can be worked on without restarting the viewer. It reads the sources from
+//! the repository, which is why it is only built with the `shader-reload` feature.
+
+use std::ffi::OsStr;
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+
+use oscuras::pathtracer::ComputeShader;
+use oscuras::Pathtracer;
+
+const SHADER_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders");
+const INCLUDE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders/include");
+
+pub struct ShaderReloader {
+    // Stops watching when dropped
+    _watcher: RecommendedWatcher,
+    events: Receiver<DebouncedEvent>,
+    compiler: shaderc::Compiler,
+    options: shaderc::CompileOptions<'static>,
+    // Device errors raised while a pipeline is being rebuilt, None the rest of the time
+    device_errors: Arc<Mutex<Option<Vec<String>>>>,
+}
+
+impl ShaderReloader {
+    pub fn new(device: &wgpu::Device) -> Result<Self> {
+        let (sender, events) = channel();
+        let mut watcher = notify::watcher(sender, Duration::from_millis(250))?;
+        watcher.watch(SHADER_DIR, RecursiveMode::Recursive)?;
+        let compiler =
+            shaderc::Compiler::new().ok_or_else(|| anyhow!("Unable to create shader compiler"))?;
+        let mut options = shaderc::CompileOptions::new()
+            .ok_or_else(|| anyhow!("Unable to create shader compile options"))?;
+        options.set_include_callback(resolve_include);
+
+        // wgpu panics on any error by default. A shader that compiles can still fail to
+        // build a pipeline, and that should be logged like a compile error instead.
+        let device_errors = Arc::new(Mutex::new(None::<Vec<String>>));
+        let handler_errors = Arc::clone(&device_errors);
+        device.on_uncaptured_error(move |error| {
+            if let Some(errors) = handler_errors.lock().unwrap().as_mut() {
+                errors.push(error.to_string());
+                return;
+            }
+            panic!("wgpu error: {}", error);
+        });
+
+        log::info!("Watching {} for shader changes", SHADER_DIR);
+        Ok(Self {
+            _watcher: watcher,
+            events,
+            compiler,
+            options,
+            device_errors,
+        })
+    }
+
+    /// Recompiles the shaders that changed since the last call and swaps their pipelines in.
+    /// Anything that fails is logged and leaves the running pipeline alone.
+    pub fn update(&mut self, device: &wgpu::Device, pathtracer: &mut Pathtracer) {
+        let mut changed = Vec::new();
+        for event in self.events.try_iter() {
+            let path = match event {
+                DebouncedEvent::Create(path)
+                | DebouncedEvent::Write(path)
+                | DebouncedEvent::Rename(_, path) => path,
+                _ => continue,
+            };
+            // Any shader may include a header, so a header change rebuilds all of them
+            let shaders = if path.extension() == Some(OsStr::new("glsl")) {
+                ComputeShader::ALL.to_vec()
+            } else {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(ComputeShader::from_file_name)
+                    .into_iter()
+                    .collect()
+            };
+            for shader in shaders {
+                if !changed.contains(&shader) {
+                    changed.push(shader);
+                }
+            }
+        }
+
+        for shader in changed {
+            match self.reload(device, pathtracer, shader) {
+                Ok(()) => log::info!("Reloaded {}", shader.file_name()),
+                Err(e) => log::error!("{:#}", e),
+            }
+        }
+    }
+
+    fn reload(
+        &mut self,
+        device: &wgpu::Device,
+        pathtracer: &mut Pathtracer,
+        shader: ComputeShader,
+    ) -> Result<()> {
+        let path = Path::new(SHADER_DIR).join(shader.file_name());
+        let source = std::fs::read_to_string(&path)
+            .with_context(|| format!("Unable to read {}", path.display()))?;
+        // Compile errors already say which file and line they are on. The full path lets
+        // relative includes be found next to the shader.
+        let spirv = self.compiler.compile_into_spirv(
+            &source,
+            shaderc::ShaderKind::Compute,
+            &path.to_string_lossy(),
+            "main",
+            Some(&self.options),
+        )?;
+
+        *self.device_errors.lock().unwrap() = Some(Vec::new());
+        let pipeline = pathtracer.create_pipeline(device, shader, spirv.as_binary_u8());
+        let errors = self
+            .device_errors
+            .lock()
+            .unwrap()
+            .take()
+            .unwrap_or_default();
+        if !errors.is_empty() {
+            return Err(anyhow!(
+                "Unable to build a pipeline from {}: {}",
+                shader.file_name(),
+                errors.join("\n")
+            ));
+        }
+
+        pathtracer.set_pipeline(shader, pipeline);
+        Ok(())
+    }
+}
+
+/// Resolves includes the same way the build script does: `"file"` next to the including file,
+/// `<file>` in the shared include directory.
+fn resolve_include(
+    requested: &str,
+    ty: shaderc::IncludeType,
+    requesting: &str,
+    _depth: usize,
+) -> shaderc::IncludeCallbackResult {
+    let path = match ty {
+        shaderc::IncludeType::Relative => Path::new(requesting)
+            .parent()
+            .unwrap_or_else(|| Path::new(SHADER_DIR))
+            .join(requested),
+        shaderc::IncludeType::Standard => Path::new(INCLUDE_DIR).join(requested),
+    };
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Unable to include {}: {}", path.display(), e))?;
+    Ok(shaderc::ResolvedInclude {
+        resolved_name: path.to_string_lossy().into_owned(),
+        content,
+    })
+}
@@ -1,5 +1,3 @@
-use bitflags;
-
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
@@ -28,40 +26,6 @@ impl Vertex {
     }
 }
 
-#[repr(C)]
-#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct Intersection {
-    surface_normal: [f32; 3],
-    t: f32,
-}
-
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct Ray {
-    origin: [f32; 3],
-    _padding: u32,
-    direction: [f32; 3],
-    _more_padding: u32,
-}
-
-bitflags::bitflags! {
-    #[repr(transparent)]
-    #[derive(bytemuck::Pod, bytemuck::Zeroable)]
-    pub struct GeomType: u32 {
-        const SPHERE = 1;
-        const BOX = 2;
-        const TRIANGLE = 4;
-    }
-}
-#[repr(C)]
-#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct Geometry {
-    pub transf: [[f32; 4]; 4],
-    pub inverse: [[f32; 4]; 4],
-    pub transp_inv: [[f32; 4]; 4],
-    pub ty: GeomType,
-}
-
 pub const VERTICES: &[Vertex] = &[
     Vertex {
         position: [1.0, 1.0, 0.0],
@@ -0,0 +1,87 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Duration;
+
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// Watches a scene file and everything it loaded, so the viewer can reload it when any of
+/// them changes.
+///
+/// The directories are watched rather than the files themselves, since a lot of editors save
+/// by replacing the file and a watch on it would go away with the old one.
+pub struct SceneWatcher {
+    path: PathBuf,
+    watcher: RecommendedWatcher,
+    events: Receiver<DebouncedEvent>,
+    directories: Vec<PathBuf>,
+    files: Vec<PathBuf>,
+}
+
+impl SceneWatcher {
+    pub fn new(path: &Path, dependencies: &[PathBuf]) -> notify::Result<Self> {
+        let (sender, events) = channel();
+        // Long enough for a save to settle, short enough to feel immediate
+        let watcher = notify::watcher(sender, Duration::from_millis(250))?;
+        let mut scene_watcher = Self {
+            path: path.to_path_buf(),
+            watcher,
+            events,
+            directories: Vec::new(),
+            files: Vec::new(),
+        };
+        scene_watcher.watch(dependencies);
+        Ok(scene_watcher)
+    }
+
+    /// The scene file to reload.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Watches `dependencies` instead of whatever was watched before.
+    pub fn watch(&mut self, dependencies: &[PathBuf]) {
+        for directory in self.directories.drain(..) {
+            // Fails if the directory is already gone, which is fine
+            let _ = self.watcher.unwatch(&directory);
+        }
+
+        // Events come with absolute paths
+        self.files = dependencies
+            .iter()
+            .filter_map(|file| file.canonicalize().ok())
+            .collect();
+        for file in &self.files {
+            let directory = match file.parent() {
+                Some(directory) => directory.to_path_buf(),
+                None => continue,
+            };
+            if self.directories.contains(&directory) {
+                continue;
+            }
+            match self.watcher.watch(&directory, RecursiveMode::NonRecursive) {
+                Ok(()) => self.directories.push(directory),
+                Err(e) => log::warn!("Unable to watch {}: {}", directory.display(), e),
+            }
+        }
+    }
+
+    /// Whether any of the watched files changed since the last call.
+    pub fn changed(&self) -> bool {
+        let mut changed = false;
+        for event in self.events.try_iter() {
+            let paths = match &event {
+                DebouncedEvent::Create(path)
+                | DebouncedEvent::Write(path)
+                | DebouncedEvent::Remove(path) => vec![path],
+                DebouncedEvent::Rename(from, to) => vec![from, to],
+                DebouncedEvent::Error(e, path) => {
+                    log::warn!("Error watching {:?}: {}", path, e);
+                    continue;
+                }
+                _ => continue,
+            };
+            changed |= paths.iter().any(|&path| self.files.contains(path));
+        }
+        changed
+    }
+}